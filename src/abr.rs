@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+/// Share of the measured throughput a rendition's bitrate may use
+const BANDWIDTH_SAFETY: f64 = 0.8;
+
+/// Weight of the newest sample in the throughput estimate
+const THROUGHPUT_SMOOTHING: f64 = 0.5;
+
+/// Picks the rendition of each segment from the measured download throughput
+#[derive(Debug, Clone)]
+pub struct AbrController {
+    /// Bitrate of each rendition in bits per second, lowest first
+    bitrates: Vec<f64>,
+    /// Smoothed throughput in bytes per second
+    throughput: Option<f64>,
+    current: usize,
+}
+
+impl AbrController {
+    /// Start on the lowest rendition until the first throughput sample arrives
+    pub fn new(bitrates: Vec<f64>) -> Self {
+        Self {
+            bitrates,
+            throughput: None,
+            current: 0,
+        }
+    }
+
+    pub fn record(&mut self, bytes: usize, elapsed: Duration) {
+        if bytes == 0 || elapsed.is_zero() {
+            return;
+        }
+
+        let sample = bytes as f64 / elapsed.as_secs_f64();
+        self.throughput = Some(match self.throughput {
+            Some(throughput) => {
+                throughput * (1.0 - THROUGHPUT_SMOOTHING) + sample * THROUGHPUT_SMOOTHING
+            }
+            None => sample,
+        });
+    }
+
+    pub fn throughput(&self) -> Option<f64> {
+        self.throughput
+    }
+
+    /// Rendition to fetch the next segment from
+    pub fn select(&mut self) -> usize {
+        if let Some(throughput) = self.throughput {
+            let budget = throughput * 8.0 * BANDWIDTH_SAFETY;
+            self.current = self
+                .bitrates
+                .iter()
+                .rposition(|bitrate| *bitrate <= budget)
+                .unwrap_or(0);
+        }
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abr_switches_with_throughput() {
+        let mut abr = AbrController::new(vec![1_000_000.0, 4_000_000.0, 8_000_000.0]);
+        assert_eq!(abr.select(), 0);
+
+        // 1MB/s is 8Mbit/s, enough for 4Mbit/s with headroom but not 8Mbit/s
+        abr.record(1_000_000, Duration::from_secs(1));
+        assert_eq!(abr.select(), 1);
+
+        // A slow segment drops back down
+        abr.record(50_000, Duration::from_secs(1));
+        abr.record(50_000, Duration::from_secs(1));
+        assert_eq!(abr.select(), 0);
+    }
+}
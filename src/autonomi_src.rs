@@ -0,0 +1,364 @@
+use crate::data_source::{RangeReader, SourceProvider};
+
+use gst::glib;
+use gst::prelude::*;
+use gstreamer as gst;
+use gstreamer_base as gst_base;
+use std::sync::OnceLock;
+
+/// URI scheme read by `autonomisrc`
+pub const URI_SCHEME: &str = "autonomi";
+
+/// Where `autonomisrc` elements get the data of their network from, and the runtime the clients live on
+static SOURCES: OnceLock<(SourceProvider, tokio::runtime::Handle)> = OnceLock::new();
+
+glib::wrapper! {
+    /// Source element reading `autonomi://<network>/<address>` with its size and seekable range reads
+    pub struct AutonomiSrc(ObjectSubclass<imp::AutonomiSrc>)
+        @extends gst_base::BaseSrc, gst::Element, gst::Object,
+        @implements gst::URIHandler;
+}
+
+/// Register `autonomisrc` so `playbin3`, `uridecodebin3` and launch strings read Autonomi data through `sources`
+pub fn register(sources: SourceProvider) -> Result<(), String> {
+    gst::init().map_err(|e| format!("Failed to initialize GStreamer: {e}"))?;
+    let runtime = tokio::runtime::Handle::try_current()
+        .map_err(|e| format!("autonomisrc needs a Tokio runtime: {e}"))?;
+    SOURCES
+        .set((sources, runtime))
+        .map_err(|_| "autonomisrc is already registered".to_string())?;
+
+    gst::Element::register(
+        None,
+        "autonomisrc",
+        gst::Rank::PRIMARY,
+        AutonomiSrc::static_type(),
+    )
+    .map_err(|e| format!("Failed to register autonomisrc: {e}"))
+}
+
+/// `autonomi://` URI of `address` on `network`
+pub fn uri(network: &str, address: &str) -> String {
+    format!("{URI_SCHEME}://{network}/{address}")
+}
+
+/// Network and address of an `autonomi://<network>/<address>` URI
+fn parse_uri(uri: &str) -> Result<(String, String), String> {
+    uri.strip_prefix(URI_SCHEME)
+        .and_then(|rest| rest.strip_prefix("://"))
+        .and_then(|path| path.trim_end_matches('/').split_once('/'))
+        .filter(|(network, address)| {
+            !network.is_empty() && !address.is_empty() && !address.contains('/')
+        })
+        .map(|(network, address)| (network.to_string(), address.to_string()))
+        .ok_or_else(|| format!("Invalid URI {uri:?}, expected {URI_SCHEME}://<network>/<address>"))
+}
+
+/// Open `address` on `network` for range reads, blocking the calling thread until it is.
+///
+/// Elements start on whichever thread changes the pipeline state, possibly a runtime worker
+/// where `block_on` panics, so the open runs as a task on the runtime. Waiting for it still
+/// blocks that worker, the runtime needs another one free to run the task.
+fn open(network: String, address: String) -> Result<Box<dyn RangeReader>, String> {
+    let (sources, runtime) = SOURCES
+        .get()
+        .ok_or_else(|| "autonomisrc is not registered".to_string())?;
+    let sources = sources.clone();
+    let (result_tx, result_rx) = std::sync::mpsc::channel();
+    runtime.spawn(async move {
+        let result = match sources(network.clone()).await {
+            Ok(Some(source)) => source.open_ranged(&address).await,
+            Ok(None) => Err(format!("Unknown network {network:?}")),
+            Err(e) => Err(e),
+        };
+        let _ = result_tx.send(result);
+    });
+    result_rx
+        .recv()
+        .map_err(|_| "Opening the data was cancelled".to_string())?
+}
+
+/// Play `uri` with `playbin3` until it ends, blocking the calling thread
+pub fn play(uri: &str, fake_sink: bool) -> Result<(), String> {
+    let playbin = gst::ElementFactory::make("playbin3")
+        .property("uri", uri)
+        .build()
+        .map_err(|e| format!("Failed to create playbin3: {e}"))?;
+    if fake_sink {
+        for property in ["video-sink", "audio-sink"] {
+            let sink = gst::ElementFactory::make("fakesink")
+                .property("sync", false)
+                .build()
+                .map_err(|e| format!("Failed to create fakesink: {e}"))?;
+            playbin.set_property(property, sink);
+        }
+    }
+
+    let bus = playbin
+        .bus()
+        .ok_or_else(|| "playbin3 has no bus".to_string())?;
+    playbin
+        .set_state(gst::State::Playing)
+        .map_err(|e| format!("Failed to start playbin3: {e}"))?;
+
+    let mut result = Ok(());
+    for msg in bus.iter_timed(gst::ClockTime::NONE) {
+        match msg.view() {
+            gst::MessageView::Eos(_) => break,
+            gst::MessageView::Error(err) => {
+                result = Err(format!("{} ({:?})", err.error(), err.debug()));
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let _ = playbin.set_state(gst::State::Null);
+    result
+}
+
+mod imp {
+    use super::*;
+    use gst::subclass::prelude::*;
+    use gst_base::prelude::*;
+    use gst_base::subclass::prelude::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    pub struct AutonomiSrc {
+        /// Network and address set through the URI or the `location` property
+        location: Mutex<Option<(String, String)>>,
+        /// Open between `start` and `stop`
+        reader: Mutex<Option<Box<dyn RangeReader>>>,
+    }
+
+    impl AutonomiSrc {
+        fn set_location(&self, uri: &str) -> Result<(), glib::Error> {
+            if self.reader.lock().unwrap().is_some() {
+                return Err(glib::Error::new(
+                    gst::URIError::BadState,
+                    "Can't change the URI of a started autonomisrc",
+                ));
+            }
+            let location =
+                parse_uri(uri).map_err(|e| glib::Error::new(gst::URIError::BadUri, &e))?;
+            *self.location.lock().unwrap() = Some(location);
+            Ok(())
+        }
+
+        fn location_uri(&self) -> Option<String> {
+            self.location
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|(network, address)| uri(network, address))
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for AutonomiSrc {
+        const NAME: &'static str = "AntubeAutonomiSrc";
+        type Type = super::AutonomiSrc;
+        type ParentType = gst_base::BaseSrc;
+        type Interfaces = (gst::URIHandler,);
+    }
+
+    impl ObjectImpl for AutonomiSrc {
+        fn properties() -> &'static [glib::ParamSpec] {
+            static PROPERTIES: OnceLock<Vec<glib::ParamSpec>> = OnceLock::new();
+            PROPERTIES.get_or_init(|| {
+                vec![glib::ParamSpecString::builder("location")
+                    .nick("Location")
+                    .blurb("autonomi://<network>/<address> URI to read")
+                    .build()]
+            })
+        }
+
+        fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+            match pspec.name() {
+                "location" => {
+                    let uri: Option<String> = value.get().expect("type checked upstream");
+                    let result = match uri {
+                        Some(uri) => self.set_location(&uri),
+                        None => {
+                            *self.location.lock().unwrap() = None;
+                            Ok(())
+                        }
+                    };
+                    if let Err(e) = result {
+                        println!("autonomisrc: {e}");
+                    }
+                }
+                name => unreachable!("autonomisrc has no property {name}"),
+            }
+        }
+
+        fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+            match pspec.name() {
+                "location" => self.location_uri().to_value(),
+                name => unreachable!("autonomisrc has no property {name}"),
+            }
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+            self.obj().set_format(gst::Format::Bytes);
+        }
+    }
+
+    impl GstObjectImpl for AutonomiSrc {}
+
+    impl ElementImpl for AutonomiSrc {
+        fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+            static METADATA: OnceLock<gst::subclass::ElementMetadata> = OnceLock::new();
+            Some(METADATA.get_or_init(|| {
+                gst::subclass::ElementMetadata::new(
+                    "Autonomi Source",
+                    "Source/Network",
+                    "Reads data stored on the Autonomi network",
+                    "AnTube",
+                )
+            }))
+        }
+
+        fn pad_templates() -> &'static [gst::PadTemplate] {
+            static PAD_TEMPLATES: OnceLock<Vec<gst::PadTemplate>> = OnceLock::new();
+            PAD_TEMPLATES.get_or_init(|| {
+                vec![gst::PadTemplate::new(
+                    "src",
+                    gst::PadDirection::Src,
+                    gst::PadPresence::Always,
+                    &gst::Caps::new_any(),
+                )
+                .unwrap()]
+            })
+        }
+    }
+
+    impl BaseSrcImpl for AutonomiSrc {
+        fn start(&self) -> Result<(), gst::ErrorMessage> {
+            let (network, address) =
+                self.location.lock().unwrap().clone().ok_or_else(|| {
+                    gst::error_msg!(gst::ResourceError::Settings, ["No URI to read"])
+                })?;
+            println!("autonomisrc opening {address} on {network}");
+
+            let reader = open(network, address)
+                .map_err(|e| gst::error_msg!(gst::ResourceError::OpenRead, ["{}", e]))?;
+            *self.reader.lock().unwrap() = Some(reader);
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), gst::ErrorMessage> {
+            *self.reader.lock().unwrap() = None;
+            Ok(())
+        }
+
+        fn is_seekable(&self) -> bool {
+            true
+        }
+
+        fn size(&self) -> Option<u64> {
+            self.reader
+                .lock()
+                .unwrap()
+                .as_ref()
+                .map(|reader| reader.data_size() as u64)
+        }
+
+        fn fill(
+            &self,
+            offset: u64,
+            length: u32,
+            buffer: &mut gst::BufferRef,
+        ) -> Result<gst::FlowSuccess, gst::FlowError> {
+            let mut reader = self.reader.lock().unwrap();
+            let reader = reader.as_mut().ok_or(gst::FlowError::Flushing)?;
+            if offset >= reader.data_size() as u64 {
+                return Err(gst::FlowError::Eos);
+            }
+
+            let data = reader
+                .read_range(offset as usize, length as usize)
+                .map_err(|e| {
+                    gst::element_imp_error!(self, gst::ResourceError::Read, ["{}", e]);
+                    gst::FlowError::Error
+                })?;
+            {
+                let mut map = buffer.map_writable().map_err(|_| gst::FlowError::Error)?;
+                map[..data.len()].copy_from_slice(&data);
+            }
+            buffer.set_size(data.len());
+            Ok(gst::FlowSuccess::Ok)
+        }
+    }
+
+    impl URIHandlerImpl for AutonomiSrc {
+        const URI_TYPE: gst::URIType = gst::URIType::Src;
+
+        fn protocols() -> &'static [&'static str] {
+            &[URI_SCHEME]
+        }
+
+        fn uri(&self) -> Option<String> {
+            self.location_uri()
+        }
+
+        fn set_uri(&self, uri: &str) -> Result<(), glib::Error> {
+            self.set_location(uri)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::{DataSource, MemorySource};
+    use gstreamer_app as gst_app;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_launch_pipeline_reads_autonomi_uri() {
+        let data: Vec<u8> = (0..100u8).collect();
+        let mut source = MemorySource::new(16);
+        source.insert("video", data.clone());
+        let source: Arc<dyn DataSource> = Arc::new(source);
+        register(Arc::new(move |network| {
+            let source = source.clone();
+            Box::pin(async move {
+                match network.as_str() {
+                    "local" => Ok(Some(source)),
+                    _ => Ok(None),
+                }
+            })
+        }))
+        .unwrap();
+
+        // uridecodebin3 and playbin3 find the element by its scheme
+        let element =
+            gst::Element::make_from_uri(gst::URIType::Src, &uri("local", "video"), None).unwrap();
+        assert_eq!(element.factory().unwrap().name().as_str(), "autonomisrc");
+        assert!(parse_uri("autonomi://video").is_err());
+
+        let pipeline = gst::parse::launch(
+            "autonomisrc location=autonomi://local/video blocksize=30 ! appsink name=sink sync=false",
+        )
+        .unwrap()
+        .downcast::<gst::Pipeline>()
+        .unwrap();
+        let sink = pipeline
+            .by_name("sink")
+            .unwrap()
+            .downcast::<gst_app::AppSink>()
+            .unwrap();
+        pipeline.set_state(gst::State::Playing).unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(sample) = sink.pull_sample() {
+            let buffer = sample.buffer().unwrap().map_readable().unwrap();
+            received.extend_from_slice(&buffer);
+        }
+        pipeline.set_state(gst::State::Null).unwrap();
+        assert_eq!(received, data);
+    }
+}
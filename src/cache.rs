@@ -0,0 +1,498 @@
+use crate::data_source::{BoxFuture, ChunkStream, DataSource, RangeReader};
+
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default size budget of the on-disk chunk cache
+pub const DEFAULT_CACHE_SIZE_MB: u64 = 2048;
+
+/// File holding the total data size of an address, next to its cached pieces
+const SIZE_FILE: &str = "size";
+
+/// Extension of pieces being written, before they are renamed into place
+const TMP_EXTENSION: &str = "tmp";
+
+/// Numbers the temporary files of this process, so concurrent writes of a piece never share one
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+struct Piece {
+    len: usize,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    /// Cached pieces per address key, by byte offset
+    pieces: HashMap<String, BTreeMap<usize, Piece>>,
+    total_size: u64,
+    /// Logical clock used to order pieces for LRU eviction
+    clock: u64,
+}
+
+/// Content-addressed cache of fetched data on disk, shared by all streams.
+///
+/// Data is stored per address as pieces keyed by their byte offset, the least
+/// recently used pieces are evicted when the cache grows past its size budget.
+pub struct ChunkCache {
+    root: PathBuf,
+    max_size: u64,
+    state: Mutex<CacheState>,
+}
+
+impl ChunkCache {
+    /// Open the cache in the user's cache directory
+    pub fn open_default(max_size_mb: u64) -> Result<Self, String> {
+        let root = dirs_next::cache_dir()
+            .ok_or_else(|| "Could not find the cache directory".to_string())?
+            .join("antube")
+            .join("chunks");
+        Self::open(root, max_size_mb * 1024 * 1024)
+    }
+
+    pub fn open(root: impl Into<PathBuf>, max_size: u64) -> Result<Self, String> {
+        let root = root.into();
+        std::fs::create_dir_all(&root)
+            .map_err(|e| format!("Failed to create cache dir {}: {e}", root.display()))?;
+
+        let cache = Self {
+            state: Mutex::new(Self::scan(&root)),
+            root,
+            max_size,
+        };
+        cache.evict();
+
+        let state = cache.state.lock().unwrap();
+        println!(
+            "Chunk cache at {} holds {} bytes (limit {} bytes)",
+            cache.root.display(),
+            state.total_size,
+            cache.max_size
+        );
+        drop(state);
+
+        Ok(cache)
+    }
+
+    /// Rebuild the index from the files on disk, oldest modified first in LRU order
+    fn scan(root: &Path) -> CacheState {
+        let mut found = Vec::new();
+
+        for dir in std::fs::read_dir(root).into_iter().flatten().flatten() {
+            let key = dir.file_name().to_string_lossy().to_string();
+            for file in std::fs::read_dir(dir.path())
+                .into_iter()
+                .flatten()
+                .flatten()
+            {
+                let name = file.file_name().to_string_lossy().to_string();
+                // Left behind by a write that never finished
+                if Path::new(&name)
+                    .extension()
+                    .is_some_and(|extension| extension.to_string_lossy().starts_with(TMP_EXTENSION))
+                {
+                    let _ = std::fs::remove_file(file.path());
+                    continue;
+                }
+                let Ok(offset) = usize::from_str_radix(&name, 16) else {
+                    continue;
+                };
+                let Ok(metadata) = file.metadata() else {
+                    continue;
+                };
+                let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+                found.push((modified, key.clone(), offset, metadata.len() as usize));
+            }
+        }
+
+        found.sort_by_key(|(modified, ..)| *modified);
+
+        let mut state = CacheState::default();
+        for (_, key, offset, len) in found {
+            state.clock += 1;
+            state.total_size += len as u64;
+            let last_used = state.clock;
+            state
+                .pieces
+                .entry(key)
+                .or_default()
+                .insert(offset, Piece { len, last_used });
+        }
+        state
+    }
+
+    /// Directory name for an address, hex addresses are used as is
+    fn key(address: &str) -> String {
+        if !address.is_empty() && address.chars().all(|c| c.is_ascii_hexdigit()) {
+            return address.to_ascii_lowercase();
+        }
+
+        // Other addresses (e.g. file paths) are hashed with FNV-1a, stable across runs
+        let hash = address.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+        format!("x{hash:016x}")
+    }
+
+    fn piece_path(&self, key: &str, offset: usize) -> PathBuf {
+        self.root.join(key).join(format!("{offset:016x}"))
+    }
+
+    pub fn data_size(&self, address: &str) -> Option<usize> {
+        let path = self.root.join(Self::key(address)).join(SIZE_FILE);
+        std::fs::read_to_string(path).ok()?.trim().parse().ok()
+    }
+
+    pub fn set_data_size(&self, address: &str, data_size: usize) {
+        let dir = self.root.join(Self::key(address));
+        if let Err(e) = std::fs::create_dir_all(&dir)
+            .and_then(|_| std::fs::write(dir.join(SIZE_FILE), data_size.to_string()))
+        {
+            println!("Failed to write cache size file: {e}");
+        }
+    }
+
+    /// The pieces covering the whole data of `address`, if it is fully cached
+    fn complete_pieces(&self, address: &str) -> Option<(usize, Vec<(usize, usize)>)> {
+        let data_size = self.data_size(address)?;
+        let state = self.state.lock().unwrap();
+        let pieces = state.pieces.get(&Self::key(address))?;
+
+        let mut layout = Vec::new();
+        let mut position = 0;
+        while position < data_size {
+            let (&offset, piece) = pieces.range(..=position).next_back()?;
+            let end = offset + piece.len;
+            if end <= position {
+                return None;
+            }
+            layout.push((offset, piece.len));
+            position = end;
+        }
+        Some((data_size, layout))
+    }
+
+    /// Read `len` bytes at `start` if the cached pieces cover that range
+    pub fn read_range(&self, address: &str, start: usize, len: usize) -> Option<Bytes> {
+        let key = Self::key(address);
+        let mut reads = Vec::new();
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let clock = state.clock;
+            let pieces = state.pieces.get_mut(&key)?;
+
+            let mut position = start;
+            while position < start + len {
+                let (&offset, piece) = pieces.range_mut(..=position).next_back()?;
+                let end = offset + piece.len;
+                if end <= position {
+                    return None;
+                }
+                piece.last_used = clock;
+
+                let read_end = end.min(start + len);
+                reads.push((offset, position - offset, read_end - position));
+                position = read_end;
+            }
+        }
+
+        // Read the files outside the lock, a piece evicted meanwhile is a miss
+        let mut data = Vec::with_capacity(len);
+        for (offset, skip, take) in reads {
+            let piece = std::fs::read(self.piece_path(&key, offset)).ok()?;
+            data.extend_from_slice(piece.get(skip..skip + take)?);
+        }
+        Some(Bytes::from(data))
+    }
+
+    /// Store a fetched piece of the data of `address` starting at `offset`
+    pub fn put(&self, address: &str, offset: usize, data: &[u8]) {
+        if data.is_empty() || data.len() as u64 > self.max_size {
+            return;
+        }
+
+        let key = Self::key(address);
+        let path = self.piece_path(&key, offset);
+
+        // Write to a temporary file and rename, so concurrent readers never see partial pieces
+        let tmp_path = path.with_extension(format!(
+            "{TMP_EXTENSION}{}-{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let write_result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&tmp_path, data))
+            .and_then(|_| std::fs::rename(&tmp_path, &path));
+        if let Err(e) = write_result {
+            println!("Failed to cache piece at offset {offset}: {e}");
+            let _ = std::fs::remove_file(&tmp_path);
+            return;
+        }
+
+        {
+            let mut state = self.state.lock().unwrap();
+            state.clock += 1;
+            let last_used = state.clock;
+            let previous = state.pieces.entry(key).or_default().insert(
+                offset,
+                Piece {
+                    len: data.len(),
+                    last_used,
+                },
+            );
+            state.total_size -= previous.map_or(0, |piece| piece.len as u64);
+            state.total_size += data.len() as u64;
+        }
+
+        self.evict();
+    }
+
+    /// Remove least recently used pieces until the cache fits its size budget
+    fn evict(&self) {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+
+        while state.total_size > self.max_size {
+            let oldest = state
+                .pieces
+                .iter()
+                .flat_map(|(key, pieces)| {
+                    pieces
+                        .iter()
+                        .map(move |(offset, piece)| (piece.last_used, key.clone(), *offset))
+                })
+                .min();
+            let Some((_, key, offset)) = oldest else {
+                break;
+            };
+
+            if let Some(pieces) = state.pieces.get_mut(&key) {
+                if let Some(piece) = pieces.remove(&offset) {
+                    state.total_size -= piece.len as u64;
+                }
+                if pieces.is_empty() {
+                    state.pieces.remove(&key);
+                }
+            }
+            let _ = std::fs::remove_file(self.piece_path(&key, offset));
+        }
+    }
+}
+
+/// Cache hits and misses of a single stream
+#[derive(Debug, Default)]
+pub struct CacheStats {
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl CacheStats {
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn record(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A data source serving from the chunk cache first and caching what it fetches
+pub struct CachedSource {
+    inner: Arc<dyn DataSource>,
+    cache: Arc<ChunkCache>,
+    stats: Arc<CacheStats>,
+}
+
+impl CachedSource {
+    pub fn new(inner: Arc<dyn DataSource>, cache: Arc<ChunkCache>, stats: Arc<CacheStats>) -> Self {
+        Self {
+            inner,
+            cache,
+            stats,
+        }
+    }
+}
+
+impl DataSource for CachedSource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let cache = self.cache.clone();
+            let stats = self.stats.clone();
+            let owned_address = address.to_string();
+
+            // Replay entirely from disk when every piece is cached
+            if let Some((data_size, layout)) = self.cache.complete_pieces(address) {
+                println!("Serving {address} from the chunk cache");
+                let chunks = layout.into_iter().map(move |(offset, len)| {
+                    let piece = cache
+                        .read_range(&owned_address, offset, len)
+                        .ok_or_else(|| format!("Cached piece at offset {offset} was evicted"))?;
+                    stats.record(true);
+                    Ok(piece)
+                });
+                return Ok(ChunkStream::new(data_size, chunks));
+            }
+
+            let stream = self.inner.open(address).await?;
+            let data_size = stream.data_size();
+            self.cache.set_data_size(address, data_size);
+
+            let mut offset = 0;
+            let chunks = stream.map(move |chunk_result| {
+                let chunk = chunk_result?;
+                cache.put(&owned_address, offset, &chunk);
+                offset += chunk.len();
+                stats.record(false);
+                Ok(chunk)
+            });
+            Ok(ChunkStream::new(data_size, chunks))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            // Only reach the inner source when the data isn't fully cached
+            let (inner, data_size) = match self.cache.complete_pieces(address) {
+                Some((data_size, _)) => (None, data_size),
+                None => {
+                    let reader = self.inner.open_ranged(address).await?;
+                    let data_size = reader.data_size();
+                    self.cache.set_data_size(address, data_size);
+                    (Some(reader), data_size)
+                }
+            };
+
+            Ok(Box::new(CachedRangeReader {
+                inner,
+                cache: self.cache.clone(),
+                stats: self.stats.clone(),
+                address: address.to_string(),
+                data_size,
+            }) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+struct CachedRangeReader {
+    inner: Option<Box<dyn RangeReader>>,
+    cache: Arc<ChunkCache>,
+    stats: Arc<CacheStats>,
+    address: String,
+    data_size: usize,
+}
+
+impl RangeReader for CachedRangeReader {
+    fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        let len = len.min(self.data_size.saturating_sub(start));
+        if let Some(data) = self.cache.read_range(&self.address, start, len) {
+            self.stats.record(true);
+            return Ok(data);
+        }
+
+        let inner = self
+            .inner
+            .as_mut()
+            .ok_or_else(|| format!("Range {start}+{len} is no longer cached"))?;
+        let data = inner.read_range(start, len)?;
+        self.cache.put(&self.address, start, &data);
+        self.stats.record(false);
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::MemorySource;
+
+    fn temp_cache_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("antube_cache_test_{name}"));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_read_range_across_pieces() {
+        let cache = ChunkCache::open(temp_cache_dir("ranges"), 1024).unwrap();
+        cache.put("abcd", 0, &[0, 1, 2, 3]);
+        cache.put("abcd", 4, &[4, 5, 6, 7]);
+
+        assert_eq!(&cache.read_range("abcd", 2, 4).unwrap()[..], &[2, 3, 4, 5]);
+        assert!(cache.read_range("abcd", 6, 4).is_none());
+        assert!(cache.read_range("other", 0, 1).is_none());
+
+        // Unfinished writes are cleaned up when the cache is opened again
+        let root = cache.root.clone();
+        let leftover = cache.piece_path("abcd", 8).with_extension("tmp1-0");
+        std::fs::write(&leftover, [8]).unwrap();
+        drop(cache);
+        let cache = ChunkCache::open(root, 1024).unwrap();
+        assert!(!leftover.exists());
+        assert_eq!(
+            &cache.read_range("abcd", 0, 8).unwrap()[..],
+            &[0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let cache = ChunkCache::open(temp_cache_dir("lru"), 8).unwrap();
+        cache.put("aa", 0, &[1; 4]);
+        cache.put("bb", 0, &[2; 4]);
+
+        // Touch "aa" so "bb" becomes the least recently used piece
+        assert!(cache.read_range("aa", 0, 4).is_some());
+        cache.put("cc", 0, &[3; 4]);
+
+        assert!(cache.read_range("aa", 0, 4).is_some());
+        assert!(cache.read_range("bb", 0, 4).is_none());
+        assert!(cache.read_range("cc", 0, 4).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_replay_from_cache() {
+        let mut memory = MemorySource::new(4);
+        memory.insert("abc", (0u8..10).collect::<Vec<_>>());
+        let inner: Arc<dyn DataSource> = Arc::new(memory);
+        let cache = Arc::new(ChunkCache::open(temp_cache_dir("replay"), 1024).unwrap());
+
+        let first_stats = Arc::new(CacheStats::default());
+        let source = CachedSource::new(inner.clone(), cache.clone(), first_stats.clone());
+        let first: Vec<_> = source
+            .open("abc")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!((first_stats.hits(), first_stats.misses()), (0, 3));
+
+        let replay_stats = Arc::new(CacheStats::default());
+        let source = CachedSource::new(inner, cache, replay_stats.clone());
+        let replay: Vec<_> = source
+            .open("abc")
+            .await
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!((replay_stats.hits(), replay_stats.misses()), (3, 0));
+        assert_eq!(first, replay);
+    }
+}
@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often subscribed channels are checked for new videos
+pub const FEED_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name the publisher's channel register key is derived from
+pub const CHANNEL_REGISTER_NAME: &str = "antube-channel";
+
+/// Length of a hex encoded register address, the owner's BLS public key
+const CHANNEL_ADDRESS_HEX_LEN: usize = 96;
+
+/// A channel followed by the user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Subscription {
+    /// Register address of the channel
+    pub address: String,
+    pub environment: String,
+    /// Videos already watched or dismissed, others show up as new in the feed
+    #[serde(default)]
+    pub seen: Vec<String>,
+}
+
+/// A video listed by a channel, with its title when the address is a metadata document
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedVideo {
+    pub address: String,
+    pub title: Option<String>,
+}
+
+/// Subscribed channels, stored as JSON in the AnTube data directory
+pub struct Subscriptions {
+    path: PathBuf,
+    channels: Vec<Subscription>,
+}
+
+impl Subscriptions {
+    pub fn open_default() -> Result<Self, String> {
+        let path = dirs_next::data_dir()
+            .ok_or_else(|| "Could not find the data directory".to_string())?
+            .join("antube")
+            .join("subscriptions.json");
+        Self::open(path)
+    }
+
+    /// Load the subscriptions at `path`, starting empty if the file doesn't exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let channels = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| format!("Invalid subscriptions file {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+        };
+        Ok(Self { path, channels })
+    }
+
+    pub fn channels(&self) -> &[Subscription] {
+        &self.channels
+    }
+
+    pub fn is_subscribed(&self, address: &str) -> bool {
+        self.channels
+            .iter()
+            .any(|channel| channel.address == address)
+    }
+
+    /// Follow a channel, returns false if it was already followed
+    pub fn subscribe(&mut self, address: &str, environment: &str) -> Result<bool, String> {
+        if !is_channel_address(address) {
+            return Err(format!("{address:?} is not a channel address"));
+        }
+        if self.is_subscribed(address) {
+            return Ok(false);
+        }
+        self.channels.push(Subscription {
+            address: address.to_string(),
+            environment: environment.to_string(),
+            seen: Vec::new(),
+        });
+        self.save().map(|()| true)
+    }
+
+    pub fn unsubscribe(&mut self, address: &str) -> Result<(), String> {
+        self.channels.retain(|channel| channel.address != address);
+        self.save()
+    }
+
+    pub fn mark_seen(&mut self, channel: &str, videos: &[String]) -> Result<(), String> {
+        let Some(subscription) = self.channels.iter_mut().find(|c| c.address == channel) else {
+            return Ok(());
+        };
+        for video in videos {
+            if !subscription.seen.contains(video) {
+                subscription.seen.push(video.clone());
+            }
+        }
+        self.save()
+    }
+
+    /// Whether a video listed by `channel` hasn't been seen yet
+    pub fn is_new(&self, channel: &str, video: &str) -> bool {
+        self.channels
+            .iter()
+            .find(|c| c.address == channel)
+            .is_some_and(|c| !c.seen.iter().any(|seen| seen == video))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+        }
+        let data = serde_json::to_vec_pretty(&self.channels)
+            .map_err(|e| format!("Failed to serialize subscriptions: {e}"))?;
+        std::fs::write(&self.path, data)
+            .map_err(|e| format!("Failed to write {}: {e}", self.path.display()))
+    }
+}
+
+fn is_channel_address(address: &str) -> bool {
+    address.len() == CHANNEL_ADDRESS_HEX_LEN && address.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscriptions_persist() {
+        let path = std::env::temp_dir().join("antube_subscriptions_test.json");
+        let _ = std::fs::remove_file(&path);
+
+        let channel = "ab".repeat(48);
+        let mut subscriptions = Subscriptions::open(&path).unwrap();
+        assert!(subscriptions.subscribe(&channel, "local").unwrap());
+        assert!(!subscriptions.subscribe(&channel, "local").unwrap());
+        subscriptions
+            .mark_seen(&channel, &["old".to_string()])
+            .unwrap();
+
+        // Anything but a hex register address is rejected
+        assert!(subscriptions.subscribe("not a channel", "local").is_err());
+        assert!(subscriptions.subscribe("ab", "local").is_err());
+
+        let subscriptions = Subscriptions::open(&path).unwrap();
+        assert_eq!(subscriptions.channels().len(), 1);
+        assert!(!subscriptions.is_new(&channel, "old"));
+        assert!(subscriptions.is_new(&channel, "new"));
+        assert!(!subscriptions.is_new("other", "new"));
+    }
+}
@@ -0,0 +1,455 @@
+use crate::autonomi_src;
+use crate::config::Config;
+use crate::container;
+use crate::data_map;
+use crate::data_source::{
+    sniff, BlockingChunks, BlockingRangeReader, DataSource, MemoryRangeReader, RangeReader, Sniffed,
+};
+use crate::gateway::Gateway;
+use crate::manifest::{Manifest, ManifestSource};
+use crate::metadata::VideoMetadata;
+use crate::playlist::Playlist;
+use crate::pool::ServerPool;
+use crate::server::{self, Server};
+use crate::uri::AntubeUri;
+use crate::video_streamer::VideoOutput;
+use crate::{AntubeApp, StreamEvent, StreamOptions, MAX_DOCUMENT_SIZE};
+
+use clap::Subcommand;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Download data to a file without the GUI
+    Download {
+        /// Data address to download
+        address: String,
+
+        /// File to write the data to
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+
+    /// Show the size, chunk count and container of the data
+    Info {
+        /// Data address to inspect
+        address: String,
+    },
+
+    /// List the videos published to a channel, newest first
+    Channel {
+        /// Channel address
+        address: String,
+    },
+
+    /// Play data, in the GUI or headless with --no-gui
+    Play {
+        /// Data address to play
+        address: String,
+
+        /// Play with native sinks without opening the GUI
+        #[arg(long)]
+        no_gui: bool,
+
+        /// Decode into fakesinks instead of displaying (implies --no-gui)
+        #[arg(long)]
+        fake_sink: bool,
+
+        /// Play through playbin3 reading an autonomi:// URI instead of the appsrc pipeline (implies --no-gui)
+        #[arg(long)]
+        playbin: bool,
+    },
+
+    /// Serve data over HTTP at /<network>/<address> for external players
+    Serve {
+        /// Port to listen on, on localhost
+        #[arg(short, long)]
+        port: Option<u16>,
+    },
+}
+
+impl Command {
+    fn address_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Command::Download { address, .. }
+            | Command::Info { address }
+            | Command::Channel { address }
+            | Command::Play { address, .. } => Some(address),
+            Command::Serve { .. } => None,
+        }
+    }
+
+    /// Whether the command runs without launching the GUI
+    pub fn is_headless(&self) -> bool {
+        match self {
+            Command::Play {
+                no_gui,
+                fake_sink,
+                playbin,
+                ..
+            } => *no_gui || *fake_sink || *playbin,
+            _ => true,
+        }
+    }
+}
+
+/// Failure categories, each with its own process exit code
+#[derive(Debug)]
+pub enum CliError {
+    /// The client could not connect to the network
+    Network(String),
+    /// The data could not be found or fetched
+    Data(String),
+    /// Local file system error
+    Io(String),
+    /// The data was fetched but could not be played
+    Playback(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Io(_) => 1,
+            CliError::Network(_) => 3,
+            CliError::Data(_) => 4,
+            CliError::Playback(_) => 5,
+        }
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CliError::Network(e) => write!(f, "Network error: {e}"),
+            CliError::Data(e) => write!(f, "Data error: {e}"),
+            CliError::Io(e) => write!(f, "I/O error: {e}"),
+            CliError::Playback(e) => write!(f, "Playback error: {e}"),
+        }
+    }
+}
+
+/// Run a headless command and return the process exit code
+pub async fn run(mut command: Command, config: &Config) -> i32 {
+    // antube:// URIs pick the network of their address
+    let mut config = config.clone();
+    // Channel addresses are register addresses, never data maps
+    let plays_data = !matches!(command, Command::Channel { .. });
+    if let Some(address) = command.address_mut() {
+        match AntubeUri::parse(address) {
+            Ok(uri) => {
+                if let Some(network) = uri.network {
+                    config.network = network;
+                }
+                if plays_data {
+                    // Private data maps, inline or in a file, play under a stand-in address
+                    match data_map::resolve_input(&uri.address) {
+                        Ok(private) => *address = private.unwrap_or(uri.address),
+                        Err(e) => {
+                            eprintln!("{e}");
+                            return 2;
+                        }
+                    }
+                } else {
+                    *address = uri.address;
+                }
+            }
+            Err(e) => {
+                eprintln!("{e}");
+                return 2;
+            }
+        }
+    }
+    let config = &config;
+
+    let result = match command {
+        Command::Download { address, output } => download(config, &address, &output).await,
+        Command::Info { address } => info(config, &address).await,
+        Command::Channel { address } => channel(config, &address).await,
+        Command::Play {
+            address,
+            fake_sink,
+            playbin: true,
+            ..
+        } => play_with_playbin(config, &address, fake_sink).await,
+        Command::Play {
+            address, fake_sink, ..
+        } => {
+            let video_output = if fake_sink {
+                VideoOutput::Fake
+            } else {
+                VideoOutput::Native
+            };
+            play(config, &address, video_output).await
+        }
+        Command::Serve { port } => serve(config, port).await,
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            e.exit_code()
+        }
+    }
+}
+
+async fn connect(config: &Config) -> Result<Server, CliError> {
+    Server::new(&config.network, &config.networks)
+        .await
+        .map_err(CliError::Network)
+}
+
+async fn channel(config: &Config, address: &str) -> Result<(), CliError> {
+    let server = connect(config).await?;
+    let videos = server.channel_feed(address).await.map_err(CliError::Data)?;
+
+    println!("Channel:   {address}");
+    println!("Videos:    {}", videos.len());
+    for video in videos {
+        match video.title {
+            Some(title) => println!("{}  {title}", video.address),
+            None => println!("{}", video.address),
+        }
+    }
+    Ok(())
+}
+
+async fn download(config: &Config, address: &str, output: &Path) -> Result<(), CliError> {
+    let server = connect(config).await?;
+    let address = match server
+        .fetch_metadata(address)
+        .await
+        .map_err(CliError::Data)?
+    {
+        Some(metadata) => metadata.content,
+        None => address.to_string(),
+    };
+
+    let source = ManifestSource::new(Arc::new(server));
+    let stream = source.open(&address).await.map_err(CliError::Data)?;
+    let total_size = stream.data_size();
+
+    let mut file = std::fs::File::create(output)
+        .map_err(|e| CliError::Io(format!("Failed to create {}: {e}", output.display())))?;
+
+    // Each chunk is a blocking fetch, taken off the runtime's workers
+    let chunks = BlockingChunks::new(stream);
+    let mut written = 0;
+    while let Some(chunk_result) = chunks.next().await {
+        let chunk = chunk_result.map_err(CliError::Data)?;
+        file.write_all(&chunk)
+            .map_err(|e| CliError::Io(format!("Failed to write {}: {e}", output.display())))?;
+
+        written += chunk.len();
+        eprintln!("Downloaded {written}/{total_size} bytes");
+    }
+
+    println!("Saved {written} bytes to {}", output.display());
+    Ok(())
+}
+
+async fn info(config: &Config, address: &str) -> Result<(), CliError> {
+    let server = connect(config).await?;
+
+    // Sniffed once to tell playlists, metadata and manifests apart
+    let mut address = address.to_string();
+    let mut sniffed = sniff(&server, &address, MAX_DOCUMENT_SIZE)
+        .await
+        .map_err(CliError::Data)?;
+
+    if let Sniffed::Small(data) = &sniffed {
+        if let Some(playlist) = Playlist::parse(data) {
+            println!("Address:   {address}");
+            println!(
+                "Playlist:  {} ({} items)",
+                playlist.name,
+                playlist.items.len()
+            );
+            for item in playlist.items {
+                match item.title {
+                    Some(title) => println!("{}  {title}", item.address),
+                    None => println!("{}", item.address),
+                }
+            }
+            return Ok(());
+        }
+
+        // Metadata is described, then the content it points at
+        if let Some(metadata) = VideoMetadata::parse(data) {
+            println!("Metadata:  {address}");
+            println!("Title:     {}", metadata.title);
+            if !metadata.description.is_empty() {
+                println!("About:     {}", metadata.description);
+            }
+            if let Some(thumbnail) = &metadata.thumbnail {
+                println!("Thumbnail: {thumbnail}");
+            }
+            address = metadata.content;
+            sniffed = sniff(&server, &address, MAX_DOCUMENT_SIZE)
+                .await
+                .map_err(CliError::Data)?;
+        }
+    }
+
+    let reader = match sniffed {
+        Sniffed::Small(data) => {
+            if let Some(manifest) = Manifest::parse(&data) {
+                println!("Address:   {address}");
+                println!("Manifest:  {} segments", manifest.segment_count());
+                println!("Size:      {} bytes", manifest.total_size());
+                println!("Duration:  {:.1}s", manifest.duration());
+                for rendition in manifest.renditions() {
+                    println!(
+                        "Rendition: {} ({:.2} Mbit/s)",
+                        rendition.name,
+                        rendition.bitrate() / 1_000_000.0
+                    );
+                }
+                return Ok(());
+            }
+            Box::new(MemoryRangeReader::new(data)) as Box<dyn RangeReader>
+        }
+        Sniffed::Large(reader) => reader,
+    };
+    let reader = BlockingRangeReader::new(reader);
+    let data_size = reader.data_size();
+
+    let head = reader
+        .read_range(0, container::SNIFF_SIZE)
+        .await
+        .map_err(CliError::Data)?;
+    let container = container::sniff(&head);
+
+    println!("Address:   {address}");
+    println!("Size:      {data_size} bytes");
+    println!("Chunks:    {}", server::chunk_count(data_size));
+    println!(
+        "Container: {}",
+        container.map_or("unknown", |container| container.name())
+    );
+    Ok(())
+}
+
+async fn play_with_playbin(
+    config: &Config,
+    address: &str,
+    fake_sink: bool,
+) -> Result<(), CliError> {
+    let pool = ServerPool::new(config.networks.clone());
+    autonomi_src::register(pool.source_provider()).map_err(CliError::Playback)?;
+
+    let uri = autonomi_src::uri(&config.network, address);
+    eprintln!("Playing {uri}");
+    tokio::task::spawn_blocking(move || autonomi_src::play(&uri, fake_sink))
+        .await
+        .map_err(|e| CliError::Playback(format!("Playback task failed: {e}")))?
+        .map_err(CliError::Playback)
+}
+
+async fn serve(config: &Config, port: Option<u16>) -> Result<(), CliError> {
+    let pool = ServerPool::new(config.networks.clone());
+    pool.spawn_health_checks();
+    pool.connect_eagerly(&config.network);
+
+    let bind = SocketAddr::from((Ipv4Addr::LOCALHOST, port.unwrap_or(config.gateway_port)));
+    let gateway = Gateway::start(bind, pool.source_provider()).map_err(CliError::Io)?;
+    println!(
+        "Open http://{}/<network>/<address> in a player, Ctrl+C to stop",
+        gateway.address()
+    );
+
+    tokio::signal::ctrl_c()
+        .await
+        .map_err(|e| CliError::Io(format!("Failed to wait for Ctrl+C: {e}")))?;
+    println!("Gateway stopped");
+    Ok(())
+}
+
+async fn play(config: &Config, address: &str, video_output: VideoOutput) -> Result<(), CliError> {
+    let server: Arc<dyn DataSource> = Arc::new(connect(config).await?);
+
+    // Playlists are replaced by their items, which play one after another
+    let mut queue = VecDeque::from([address.to_string()]);
+    while let Some(address) = queue.pop_front() {
+        if let Some(playlist) = play_address(config, server.clone(), address, video_output).await? {
+            eprintln!(
+                "Playing playlist {} ({} items)",
+                playlist.name,
+                playlist.items.len()
+            );
+            for item in playlist.items.into_iter().rev() {
+                queue.push_front(item.address);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Play `address` as the GUI does, following metadata to its content.
+/// Returns the playlist instead of playing when the address is one.
+async fn play_address(
+    config: &Config,
+    server: Arc<dyn DataSource>,
+    address: String,
+    video_output: VideoOutput,
+) -> Result<Option<Playlist>, CliError> {
+    let options = StreamOptions {
+        video_output,
+        retry: config.retry_policy(),
+        prebuffer_seconds: config.prebuffer_seconds,
+        start_paused: false,
+        start_offset: 0,
+        pipeline: config.pipeline.clone(),
+        cancelled: Default::default(),
+    };
+
+    let (server_tx, server_rx) = mpsc::unbounded_channel();
+    let _ = server_tx.send(Ok(server));
+    let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+    let task = tokio::spawn(AntubeApp::run_streaming_task(
+        1, server_rx, stream_tx, address, options,
+    ));
+
+    let mut video_streamer = None;
+    let result = loop {
+        match stream_rx.recv().await {
+            Some(StreamEvent::MetadataLoaded { metadata, .. }) => {
+                eprintln!("Playing {} from {}", metadata.title, metadata.content);
+            }
+            Some(StreamEvent::PlaylistLoaded { playlist, .. }) => break Ok(Some(playlist)),
+            Some(StreamEvent::ServerConnected { total_size, .. }) => {
+                eprintln!("Streaming {total_size} bytes");
+            }
+            Some(StreamEvent::VideoStreamerReady {
+                video_streamer: streamer,
+                ..
+            }) => {
+                eprintln!("Playback started");
+                video_streamer = Some(streamer);
+            }
+            Some(StreamEvent::StreamComplete { .. }) => eprintln!("Download complete"),
+            Some(StreamEvent::Retrying {
+                attempt,
+                max_retries,
+                error,
+                ..
+            }) => eprintln!("Retrying ({attempt}/{max_retries}): {error}"),
+            Some(StreamEvent::PlaybackFinished { .. }) => break Ok(None),
+            Some(StreamEvent::PipelineError { error, .. }) => break Err(CliError::Playback(error)),
+            Some(StreamEvent::StreamError { error, .. }) => break Err(CliError::Data(error)),
+            Some(_) => {}
+            None => break Err(CliError::Data("Stream ended before playback".to_string())),
+        }
+    };
+
+    task.abort();
+    if let Some(streamer) = video_streamer {
+        let _ = streamer.stop();
+    }
+    result
+}
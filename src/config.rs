@@ -0,0 +1,215 @@
+use crate::cache::DEFAULT_CACHE_SIZE_MB;
+use crate::gateway::DEFAULT_GATEWAY_PORT;
+use crate::network::{self, Network, NetworkDefinition, DEFAULT_NETWORK};
+use crate::prebuffer::DEFAULT_PREBUFFER_SECONDS;
+use crate::retry::{RetryPolicy, DEFAULT_MAX_RETRIES, DEFAULT_RETRY_BACKOFF_MS};
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Smallest appsrc queue that still holds a few chunks of a low bitrate video
+const MIN_APPSRC_MAX_BYTES: u64 = 256 * 1024; // 256KB
+
+/// Longest decodebin queue, far beyond any useful buffer and safe to convert to nanoseconds
+pub const MAX_DECODEBIN_MAX_SIZE_MS: u64 = 60 * 60 * 1000; // 1 hour
+
+/// Defaults and tunables, stored as JSON in the AnTube config directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Network selected at startup
+    pub network: String,
+    /// Connect to `network` when the GUI opens rather than when the first stream starts
+    pub connect_on_startup: bool,
+    /// Show videos in a separate native window instead of inside the app
+    pub native_video: bool,
+    pub cache_enabled: bool,
+    pub cache_size_mb: u64,
+    pub max_retries: u32,
+    pub retry_backoff_ms: u64,
+    /// Seconds of playback buffered before starting, when the bitrate is known
+    pub prebuffer_seconds: f64,
+    /// Local port of the HTTP gateway for external players
+    pub gateway_port: u16,
+    pub pipeline: PipelineSettings,
+    /// Networks selectable by name besides the built-in ones
+    pub networks: Vec<NetworkDefinition>,
+}
+
+/// Buffer limits and sinks of the GStreamer playback pipeline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PipelineSettings {
+    /// Bytes queued inside appsrc before pushing more blocks
+    pub appsrc_max_bytes: u64,
+    /// Size limit of each decodebin internal queue
+    pub decodebin_max_size_bytes: u32,
+    /// Duration limit of each decodebin internal queue, in milliseconds
+    pub decodebin_max_size_ms: u64,
+    /// Sink of the native video window
+    pub video_sink: String,
+    pub audio_sink: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            network: DEFAULT_NETWORK.to_string(),
+            connect_on_startup: true,
+            native_video: false,
+            cache_enabled: true,
+            cache_size_mb: DEFAULT_CACHE_SIZE_MB,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_backoff_ms: DEFAULT_RETRY_BACKOFF_MS,
+            prebuffer_seconds: DEFAULT_PREBUFFER_SECONDS,
+            gateway_port: DEFAULT_GATEWAY_PORT,
+            pipeline: PipelineSettings::default(),
+            networks: Vec::new(),
+        }
+    }
+}
+
+impl Default for PipelineSettings {
+    fn default() -> Self {
+        Self {
+            appsrc_max_bytes: 5 * 1024 * 1024,         // 5MB
+            decodebin_max_size_bytes: 2 * 1024 * 1024, // 2MB
+            decodebin_max_size_ms: 2000,
+            video_sink: "glimagesink".to_string(),
+            audio_sink: "autoaudiosink".to_string(),
+        }
+    }
+}
+
+impl Config {
+    pub fn default_path() -> Result<PathBuf, String> {
+        Ok(dirs_next::config_dir()
+            .ok_or_else(|| "Could not find the config directory".to_string())?
+            .join("antube")
+            .join("config.json"))
+    }
+
+    /// Load the config at `path`, defaults if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match std::fs::read(path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| format!("Invalid config file {}: {e}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("Failed to read {}: {e}", path.display())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+        }
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| format!("Failed to serialize config: {e}"))?;
+        std::fs::write(path, data).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+
+    /// Check every setting, listing all the invalid ones
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if let Err(e) = network::validate(&self.networks) {
+            errors.push(e);
+        }
+        if let Err(e) = Network::resolve(&self.network, &self.networks) {
+            errors.push(e);
+        }
+        if self.cache_enabled && self.cache_size_mb == 0 {
+            errors.push("cache_size_mb must be above 0, disable the cache instead".to_string());
+        }
+        if !(self.prebuffer_seconds.is_finite() && self.prebuffer_seconds > 0.0) {
+            errors.push(format!(
+                "prebuffer_seconds must be above 0, got {}",
+                self.prebuffer_seconds
+            ));
+        }
+        if self.gateway_port == 0 {
+            errors.push("gateway_port must be above 0".to_string());
+        }
+
+        let pipeline = &self.pipeline;
+        if pipeline.appsrc_max_bytes < MIN_APPSRC_MAX_BYTES {
+            errors.push(format!(
+                "pipeline.appsrc_max_bytes must be at least {MIN_APPSRC_MAX_BYTES}, got {}",
+                pipeline.appsrc_max_bytes
+            ));
+        }
+        if pipeline.decodebin_max_size_bytes == 0 {
+            errors.push("pipeline.decodebin_max_size_bytes must be above 0".to_string());
+        }
+        if !(1..=MAX_DECODEBIN_MAX_SIZE_MS).contains(&pipeline.decodebin_max_size_ms) {
+            errors.push(format!(
+                "pipeline.decodebin_max_size_ms must be between 1 and {MAX_DECODEBIN_MAX_SIZE_MS}, got {}",
+                pipeline.decodebin_max_size_ms
+            ));
+        }
+        if pipeline.video_sink.trim().is_empty() {
+            errors.push("pipeline.video_sink must name a GStreamer element".to_string());
+        }
+        if pipeline.audio_sink.trim().is_empty() {
+            errors.push("pipeline.audio_sink must name a GStreamer element".to_string());
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    /// Names of the networks to pick from, built in and custom
+    pub fn network_names(&self) -> Vec<String> {
+        network::names(&self.networks)
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: self.max_retries,
+            initial_backoff: Duration::from_millis(self.retry_backoff_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_load_and_validate() {
+        let path = std::env::temp_dir().join("antube_config_test.json");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(Config::load(&path).unwrap(), Config::default());
+        assert!(Config::default().validate().is_ok());
+
+        // Missing settings keep their defaults
+        std::fs::write(
+            &path,
+            br#"{"network": "local", "pipeline": {"audio_sink": "pulsesink"}}"#,
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.network, "local");
+        assert_eq!(config.pipeline.audio_sink, "pulsesink");
+        assert_eq!(config.max_retries, DEFAULT_MAX_RETRIES);
+
+        std::fs::write(&path, br#"{"prebufer_seconds": 4}"#).unwrap();
+        assert!(Config::load(&path).is_err());
+
+        let invalid = Config {
+            network: "moon".to_string(),
+            prebuffer_seconds: 0.0,
+            ..Config::default()
+        };
+        let error = invalid.validate().unwrap_err();
+        assert!(error.contains("moon") && error.contains("prebuffer_seconds"));
+
+        let mut invalid = Config::default();
+        invalid.pipeline.decodebin_max_size_ms = u64::MAX;
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,342 @@
+/// Bytes needed from the start of the data to recognise its container
+pub const SNIFF_SIZE: usize = 64 * 1024;
+
+/// A media container format recognised from its leading bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    WebM,
+    Matroska,
+    Avi,
+    MpegTs,
+    Ogg,
+    Flv,
+    Mp3,
+}
+
+impl Container {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "MP4",
+            Container::WebM => "WebM",
+            Container::Matroska => "Matroska",
+            Container::Avi => "AVI",
+            Container::MpegTs => "MPEG-TS",
+            Container::Ogg => "Ogg",
+            Container::Flv => "FLV",
+            Container::Mp3 => "MP3",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Container::Mp4 => "video/mp4",
+            Container::WebM => "video/webm",
+            Container::Matroska => "video/x-matroska",
+            Container::Avi => "video/x-msvideo",
+            Container::MpegTs => "video/mp2t",
+            Container::Ogg => "video/ogg",
+            Container::Flv => "video/x-flv",
+            Container::Mp3 => "audio/mpeg",
+        }
+    }
+}
+
+/// Detect the container from the first bytes of the data
+pub fn sniff(data: &[u8]) -> Option<Container> {
+    if data.len() >= 8 && &data[4..8] == b"ftyp" {
+        return Some(Container::Mp4);
+    }
+
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        // The EBML header names the doctype, WebM is a Matroska subset
+        let header = &data[..data.len().min(64)];
+        if header.windows(4).any(|w| w == b"webm") {
+            return Some(Container::WebM);
+        }
+        return Some(Container::Matroska);
+    }
+
+    if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"AVI " {
+        return Some(Container::Avi);
+    }
+
+    if data.starts_with(b"OggS") {
+        return Some(Container::Ogg);
+    }
+
+    if data.starts_with(b"FLV") {
+        return Some(Container::Flv);
+    }
+
+    if data.starts_with(b"ID3") || (data.len() >= 2 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0) {
+        return Some(Container::Mp3);
+    }
+
+    // Transport stream packets are 188 bytes long and start with a sync byte
+    const TS_PACKET_SIZE: usize = 188;
+    if data.len() >= TS_PACKET_SIZE * 3 && (0..3).all(|i| data[i * TS_PACKET_SIZE] == 0x47) {
+        return Some(Container::MpegTs);
+    }
+
+    None
+}
+
+/// Playback duration read from the container header at the start of the data
+pub fn duration_seconds(head: &[u8]) -> Option<f64> {
+    match sniff(head)? {
+        Container::Mp4 => mp4_duration(head),
+        Container::WebM | Container::Matroska => matroska_duration(head),
+        _ => None,
+    }
+}
+
+/// A top-level MP4 box, with offsets relative to the start of the data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mp4Box {
+    pub kind: [u8; 4],
+    pub offset: usize,
+    pub header_size: usize,
+    /// Size of the whole box, `None` when it extends to the end of the data
+    pub size: Option<usize>,
+}
+
+/// Walk the boxes of `data`, stopping at the first one whose header isn't complete
+/// or whose size runs past any possible offset
+pub fn mp4_boxes(data: &[u8]) -> Vec<Mp4Box> {
+    let mut boxes = Vec::new();
+    let mut offset: usize = 0;
+
+    while let Some(header) = offset.checked_add(8).and_then(|end| data.get(offset..end)) {
+        let size32 = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let kind = [header[4], header[5], header[6], header[7]];
+
+        let (header_size, size) = match size32 {
+            0 => (8, None),
+            1 => {
+                let Some(large) = data.get(offset + 8..offset + 16) else {
+                    break;
+                };
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(large);
+                (16, Some(u64::from_be_bytes(bytes) as usize))
+            }
+            size => (8, Some(size)),
+        };
+        if size.is_some_and(|size| size < header_size) {
+            break;
+        }
+
+        boxes.push(Mp4Box {
+            kind,
+            offset,
+            header_size,
+            size,
+        });
+        match size.and_then(|size| offset.checked_add(size)) {
+            Some(next) => offset = next,
+            None => break,
+        }
+    }
+
+    boxes
+}
+
+/// Where the boxes after the first `mdat` start, when the `moov` box comes after it
+pub fn mp4_trailing_moov(head: &[u8], data_size: usize) -> Option<usize> {
+    if sniff(head)? != Container::Mp4 {
+        return None;
+    }
+
+    for mp4_box in mp4_boxes(head) {
+        match &mp4_box.kind {
+            b"moov" => return None,
+            b"mdat" => {
+                let end = mp4_box.offset.checked_add(mp4_box.size?)?;
+                return (end < data_size).then_some(end);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn mp4_duration(head: &[u8]) -> Option<f64> {
+    let moov = mp4_boxes(head).into_iter().find(|b| &b.kind == b"moov")?;
+    let start = moov.offset + moov.header_size;
+    let end = moov
+        .size
+        .map_or(head.len(), |size| moov.offset.saturating_add(size))
+        .min(head.len());
+    let body = head.get(start..end)?;
+
+    let mvhd = mp4_boxes(body).into_iter().find(|b| &b.kind == b"mvhd")?;
+    let fields = body.get(mvhd.offset + mvhd.header_size..)?;
+
+    // Version 1 uses 64-bit times and duration
+    let read_u32 = |at: usize| -> Option<u64> {
+        let bytes = fields.get(at..at + 4)?;
+        Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as u64)
+    };
+    let (timescale, duration) = if *fields.first()? == 1 {
+        let bytes = fields.get(24..32)?;
+        let mut duration = [0u8; 8];
+        duration.copy_from_slice(bytes);
+        (read_u32(20)?, u64::from_be_bytes(duration))
+    } else {
+        (read_u32(12)?, read_u32(16)?)
+    };
+
+    (timescale > 0 && duration > 0).then(|| duration as f64 / timescale as f64)
+}
+
+/// Read an EBML variable length integer, returning its value and length
+fn read_vint(data: &[u8], keep_marker: bool) -> Option<(u64, usize)> {
+    let first = *data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 {
+        return None;
+    }
+
+    let mut value = if keep_marker {
+        first as u64
+    } else {
+        (first as u64) & (0xFF >> len)
+    };
+    for byte in data.get(1..len)? {
+        value = (value << 8) | *byte as u64;
+    }
+    Some((value, len))
+}
+
+fn matroska_duration(head: &[u8]) -> Option<f64> {
+    const SEGMENT: u64 = 0x18538067;
+    const INFO: u64 = 0x1549A966;
+    const CLUSTER: u64 = 0x1F43B675;
+    const TIMECODE_SCALE: u64 = 0x2AD7B1;
+    const DURATION: u64 = 0x4489;
+
+    let mut offset = 0;
+    let mut timecode_scale = 1_000_000u64;
+    let mut duration = None;
+    let mut info_end = None;
+
+    while offset < head.len() {
+        let (id, id_len) = read_vint(&head[offset..], true)?;
+        let (size, size_len) = read_vint(&head[offset + id_len..], false)?;
+        let body = offset + id_len + size_len;
+
+        match id {
+            // Step into the containers holding the duration
+            SEGMENT => offset = body,
+            INFO => {
+                info_end = Some(body.saturating_add(size as usize));
+                offset = body;
+            }
+            CLUSTER => break,
+            TIMECODE_SCALE | DURATION => {
+                let value = head.get(body..body.checked_add(size as usize)?)?;
+                if id == TIMECODE_SCALE {
+                    timecode_scale = value.iter().fold(0, |acc, b| (acc << 8) | *b as u64);
+                } else {
+                    duration = match value.len() {
+                        4 => Some(f32::from_be_bytes(value.try_into().ok()?) as f64),
+                        8 => Some(f64::from_be_bytes(value.try_into().ok()?)),
+                        _ => None,
+                    };
+                }
+                offset = body + size as usize;
+            }
+            _ => offset = body.checked_add(size as usize)?,
+        }
+
+        if info_end.is_some_and(|end| offset >= end) {
+            break;
+        }
+    }
+
+    let seconds = duration? * timecode_scale as f64 / 1e9;
+    (seconds > 0.0).then_some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_containers() {
+        let mp4 = [
+            0, 0, 0, 0x20, b'f', b't', b'y', b'p', b'i', b's', b'o', b'm',
+        ];
+        assert_eq!(sniff(&mp4), Some(Container::Mp4));
+
+        let mut webm = vec![0x1A, 0x45, 0xDF, 0xA3, 0x9F, 0x42, 0x82, 0x84];
+        webm.extend_from_slice(b"webm");
+        assert_eq!(sniff(&webm), Some(Container::WebM));
+
+        let mut ts = vec![0u8; 188 * 3];
+        for i in 0..3 {
+            ts[i * 188] = 0x47;
+        }
+        assert_eq!(sniff(&ts), Some(Container::MpegTs));
+
+        assert_eq!(sniff(b"RIFF\0\0\0\0AVI LIST"), Some(Container::Avi));
+        assert_eq!(sniff(b"hello world"), None);
+    }
+
+    #[test]
+    fn test_duration_seconds() {
+        // ftyp, then moov holding a version 0 mvhd with timescale 1000 and duration 90s
+        let mut mp4 = vec![0, 0, 0, 16];
+        mp4.extend_from_slice(b"ftypisom\0\0\0\0");
+        let mut mvhd = vec![0u8; 20];
+        mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&90_000u32.to_be_bytes());
+        mp4.extend_from_slice(&(8 + 8 + mvhd.len() as u32).to_be_bytes());
+        mp4.extend_from_slice(b"moov");
+        mp4.extend_from_slice(&(8 + mvhd.len() as u32).to_be_bytes());
+        mp4.extend_from_slice(b"mvhd");
+        mp4.extend_from_slice(&mvhd);
+        assert_eq!(duration_seconds(&mp4), Some(90.0));
+
+        // EBML header, then a segment of unknown size holding info with a 12.5s duration
+        let mut mkv = vec![0x1A, 0x45, 0xDF, 0xA3, 0x80];
+        mkv.extend_from_slice(&[
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ]);
+        mkv.extend_from_slice(&[0x15, 0x49, 0xA9, 0x66, 0x8E]);
+        mkv.extend_from_slice(&[0x2A, 0xD7, 0xB1, 0x83, 0x0F, 0x42, 0x40]);
+        mkv.extend_from_slice(&[0x44, 0x89, 0x84]);
+        mkv.extend_from_slice(&12_500f32.to_be_bytes());
+        assert_eq!(duration_seconds(&mkv), Some(12.5));
+
+        assert_eq!(duration_seconds(b"OggS"), None);
+    }
+
+    #[test]
+    fn test_mp4_trailing_moov() {
+        let mut head = vec![0, 0, 0, 16];
+        head.extend_from_slice(b"ftypisom\0\0\0\0");
+        head.extend_from_slice(&1000u32.to_be_bytes());
+        head.extend_from_slice(b"mdat");
+        head.extend_from_slice(&[0u8; 64]);
+
+        assert_eq!(mp4_trailing_moov(&head, 2000), Some(1016));
+        // Nothing after the mdat, so there is no moov to fetch
+        assert_eq!(mp4_trailing_moov(&head, 1016), None);
+
+        // A 64-bit largesize running past any offset ends the walk
+        let mut head = vec![0, 0, 0, 16];
+        head.extend_from_slice(b"ftypisom\0\0\0\0");
+        head.extend_from_slice(&1u32.to_be_bytes());
+        head.extend_from_slice(b"mdat");
+        head.extend_from_slice(&u64::MAX.to_be_bytes());
+        head.extend_from_slice(&[0u8; 64]);
+
+        let boxes = mp4_boxes(&head);
+        assert_eq!(boxes.len(), 2);
+        assert_eq!(boxes[1].size, Some(u64::MAX as usize));
+        assert_eq!(mp4_trailing_moov(&head, 2000), None);
+        assert_eq!(duration_seconds(&head), None);
+    }
+}
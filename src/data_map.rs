@@ -0,0 +1,150 @@
+use self_encryption::DataMap;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+/// Prefix of the stand-in addresses private data is streamed under
+pub const PRIVATE_PREFIX: &str = "private:";
+
+/// Length of a hex encoded public data address, data maps are longer
+const PUBLIC_ADDRESS_HEX_LEN: usize = 64;
+
+/// Data maps opened this session by stand-in address, so only the stand-in reaches logs, the UI and the cache
+fn data_maps() -> &'static Mutex<HashMap<String, String>> {
+    static DATA_MAPS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+    DATA_MAPS.get_or_init(Default::default)
+}
+
+/// Stand-in address of `input` if it is a hex encoded private data map or a file holding one, `None` otherwise
+pub fn resolve_input(input: &str) -> Result<Option<String>, String> {
+    let input = input.trim();
+    if is_data_map_hex(input) {
+        return Ok(Some(register(input)));
+    }
+    Ok(read_file(input)?.map(|data_map| register(&data_map)))
+}
+
+/// Hex data map held in the file at `path`, `None` if `path` doesn't look like a path or there is no such file
+pub fn read_file(path: &str) -> Result<Option<String>, String> {
+    let path = path.trim();
+    if !looks_like_path(path) {
+        return Ok(None);
+    }
+    let path = Path::new(path);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read data map file {}: {e}", path.display()))?;
+    let contents = contents.trim();
+    if !is_data_map_hex(contents) {
+        return Err(format!(
+            "{} doesn't hold a hex encoded data map",
+            path.display()
+        ));
+    }
+    Ok(Some(contents.to_string()))
+}
+
+/// Remember `data_map` and return its stand-in address, the same for the same data map
+fn register(data_map: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    data_map.hash(&mut hasher);
+    let address = format!("{PRIVATE_PREFIX}{:016x}", hasher.finish());
+    data_maps()
+        .lock()
+        .unwrap()
+        .insert(address.clone(), data_map.to_string());
+    address
+}
+
+pub fn is_private(address: &str) -> bool {
+    address.starts_with(PRIVATE_PREFIX)
+}
+
+/// Hex data map behind a stand-in address, `None` if it wasn't opened this session
+pub fn lookup(address: &str) -> Option<String> {
+    data_maps().lock().unwrap().get(address).cloned()
+}
+
+/// `input` for logs, with a data map, bare or in a URI, replaced by a placeholder
+pub fn redact(input: &str) -> &str {
+    let last_segment = input.trim().rsplit('/').next().unwrap_or_default();
+    if is_data_map_hex(last_segment.split('?').next().unwrap_or_default()) {
+        "<private data map>"
+    } else {
+        input
+    }
+}
+
+/// Whether `input` is hex that decodes to a serialized data map, as private uploads hand them out
+fn is_data_map_hex(input: &str) -> bool {
+    if input.len() <= PUBLIC_ADDRESS_HEX_LEN {
+        return false;
+    }
+    decode_hex(input).is_some_and(|bytes| rmp_serde::from_slice::<DataMap>(&bytes).is_ok())
+}
+
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    if !input.len().is_multiple_of(2) || !input.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    (0..input.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&input[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// Whether `input` names a file rather than an address, hex addresses have no separators or extension
+fn looks_like_path(input: &str) -> bool {
+    input.contains(['/', '\\']) || Path::new(input).extension().is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hex of the data map self-encryption makes for some data
+    fn data_map_hex() -> String {
+        let data = bytes::Bytes::from(vec![7u8; 64 * 1024]);
+        let (data_map, _) = self_encryption::encrypt(data).unwrap();
+        let bytes = rmp_serde::to_vec(&data_map).unwrap();
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn test_resolve_data_map_input() {
+        let data_map = data_map_hex();
+        let address = resolve_input(&format!(" {data_map}\n")).unwrap().unwrap();
+        assert!(is_private(&address) && !address.contains(&data_map));
+        assert_eq!(lookup(&address), Some(data_map.clone()));
+        assert_eq!(redact(&data_map), "<private data map>");
+        assert_eq!(
+            redact(&format!("antube://local/{data_map}?t=5")),
+            "<private data map>"
+        );
+
+        // Files holding the data map open the same data
+        let path = std::env::temp_dir().join("antube_data_map_test.hex");
+        std::fs::write(&path, format!("{data_map}\n")).unwrap();
+        let from_file = resolve_input(path.to_str().unwrap()).unwrap();
+        assert_eq!(from_file, Some(address));
+
+        // Only inputs that look like paths are read as files
+        assert!(looks_like_path(path.to_str().unwrap()));
+        assert!(looks_like_path("video.hex") && !looks_like_path(&data_map));
+
+        // Public addresses are left alone
+        let public = "d8949e2bd7bc0f60d6062510b4f98c9fd92a3bd70567ab9e43f79eb9f8aa24e6";
+        assert_eq!(resolve_input(public), Ok(None));
+        assert_eq!(redact(public), public);
+        assert_eq!(lookup("private:0000000000000000"), None);
+
+        // Long hex that isn't a data map, like a channel address, is left alone too
+        let channel = "ab".repeat(48);
+        assert_eq!(resolve_input(&channel), Ok(None));
+        assert_eq!(resolve_input(&"ab".repeat(200)), Ok(None));
+    }
+}
@@ -0,0 +1,685 @@
+use bytes::Bytes;
+#[cfg(test)]
+use std::collections::HashMap;
+#[cfg(test)]
+use std::fs::File;
+use std::future::Future;
+#[cfg(test)]
+use std::io::{Read, Seek, SeekFrom};
+#[cfg(test)]
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Default size of the chunks yielded by the in-memory and file sources
+pub const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024; // 1MB
+
+/// Size of the random access reads data streams in once opened for probing, seeking or resuming
+pub const RANGE_CHUNK_SIZE: usize = 4 * 1024 * 1024; // 4MB
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Data source of a network by name, connected on demand, `None` for networks that aren't defined
+pub type SourceProvider = Arc<
+    dyn Fn(String) -> BoxFuture<'static, Result<Option<Arc<dyn DataSource>>, String>> + Send + Sync,
+>;
+
+/// Something the streaming path can read video bytes from.
+///
+/// The Autonomi `Server` is the production implementation, the test-only `MemorySource`
+/// and `FileSource` let tests run the streaming path without a network.
+pub trait DataSource: Send + Sync {
+    /// Open the data at `address` and return a stream of its chunks
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>>;
+
+    /// Open the data at `address` for random access reads
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>>;
+}
+
+/// Random access over the bytes at an address
+pub trait RangeReader: Send {
+    fn data_size(&self) -> usize;
+
+    /// Read up to `len` bytes starting at `start`, shorter only at the end of the data
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String>;
+}
+
+/// The data at an address, read whole when it is small enough to be a document
+pub enum Sniffed {
+    Small(Bytes),
+    /// Larger data, with the reader it was sized through
+    Large(Box<dyn RangeReader>),
+}
+
+/// Open the data at `address` and read it whole if it is at most `max_size` bytes
+pub async fn sniff(
+    source: &dyn DataSource,
+    address: &str,
+    max_size: usize,
+) -> Result<Sniffed, String> {
+    let reader = source.open_ranged(address).await?;
+    let data_size = reader.data_size();
+    if data_size > max_size {
+        return Ok(Sniffed::Large(reader));
+    }
+
+    BlockingRangeReader::new(reader)
+        .read_range(0, data_size)
+        .await
+        .map(Sniffed::Small)
+}
+
+/// Read the whole data at `address` if it is at most `max_size` bytes, `None` if it is larger
+pub async fn read_small(
+    source: &dyn DataSource,
+    address: &str,
+    max_size: usize,
+) -> Result<Option<Bytes>, String> {
+    Ok(match sniff(source, address, max_size).await? {
+        Sniffed::Small(data) => Some(data),
+        Sniffed::Large(_) => None,
+    })
+}
+
+type Chunks = Box<dyn Iterator<Item = Result<Bytes, String>> + Send>;
+
+/// A `RangeReader` for async code, reads wait on the network so they run on the blocking thread pool
+#[derive(Clone)]
+pub struct BlockingRangeReader {
+    reader: Arc<Mutex<Box<dyn RangeReader>>>,
+    /// Size as of the last read, without waiting for a read in progress
+    data_size: Arc<AtomicUsize>,
+}
+
+impl BlockingRangeReader {
+    pub fn new(reader: Box<dyn RangeReader>) -> Self {
+        Self {
+            data_size: Arc::new(AtomicUsize::new(reader.data_size())),
+            reader: Arc::new(Mutex::new(reader)),
+        }
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.data_size.load(Ordering::Relaxed)
+    }
+
+    pub async fn read_range(&self, start: usize, len: usize) -> Result<Bytes, String> {
+        let reader = self.reader.clone();
+        let data_size = self.data_size.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut reader = reader.lock().unwrap();
+            let result = reader.read_range(start, len);
+            data_size.store(reader.data_size(), Ordering::Relaxed);
+            result
+        })
+        .await
+        .map_err(|e| format!("Range read task failed: {e}"))?
+    }
+}
+
+/// Chunks of a stream for async code, each taken on the blocking thread pool
+pub struct BlockingChunks(Arc<Mutex<Chunks>>);
+
+impl BlockingChunks {
+    pub fn new(chunks: impl Iterator<Item = Result<Bytes, String>> + Send + 'static) -> Self {
+        Self(Arc::new(Mutex::new(Box::new(chunks))))
+    }
+
+    pub async fn next(&self) -> Option<Result<Bytes, String>> {
+        let chunks = self.0.clone();
+        tokio::task::spawn_blocking(move || chunks.lock().unwrap().next())
+            .await
+            .unwrap_or_else(|e| Some(Err(format!("Chunk fetch task failed: {e}"))))
+    }
+}
+
+/// A linear stream of chunks with the total data size known upfront
+pub struct ChunkStream {
+    data_size: usize,
+    chunks: Chunks,
+}
+
+impl ChunkStream {
+    pub fn new(
+        data_size: usize,
+        chunks: impl Iterator<Item = Result<Bytes, String>> + Send + 'static,
+    ) -> Self {
+        Self {
+            data_size,
+            chunks: Box::new(chunks),
+        }
+    }
+
+    pub fn data_size(&self) -> usize {
+        self.data_size
+    }
+}
+
+impl Iterator for ChunkStream {
+    type Item = Result<Bytes, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.chunks.next()
+    }
+}
+
+/// Yields consecutive ranges of a `RangeReader` as chunks, from `start` to the end of the data
+pub struct RangeChunks {
+    reader: Box<dyn RangeReader>,
+    position: usize,
+    chunk_size: usize,
+}
+
+impl RangeChunks {
+    pub fn new(reader: Box<dyn RangeReader>, start: usize, chunk_size: usize) -> Self {
+        Self {
+            reader,
+            position: start,
+            chunk_size: chunk_size.max(1),
+        }
+    }
+}
+
+impl Iterator for RangeChunks {
+    type Item = Result<Bytes, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.reader.data_size() {
+            return None;
+        }
+
+        let chunk = self.reader.read_range(self.position, self.chunk_size);
+        if let Ok(chunk) = &chunk {
+            if chunk.is_empty() {
+                return None;
+            }
+            self.position += chunk.len();
+        }
+        Some(chunk)
+    }
+}
+
+#[cfg(test)]
+struct MemoryEntry {
+    data: Bytes,
+    fail_after_chunks: Option<usize>,
+}
+
+/// Serves data from memory, keyed by address
+#[cfg(test)]
+pub struct MemorySource {
+    entries: HashMap<String, MemoryEntry>,
+    chunk_size: usize,
+}
+
+#[cfg(test)]
+impl MemorySource {
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            chunk_size: chunk_size.max(1),
+        }
+    }
+
+    pub fn insert(&mut self, address: &str, data: impl Into<Bytes>) {
+        self.entries.insert(
+            address.to_string(),
+            MemoryEntry {
+                data: data.into(),
+                fail_after_chunks: None,
+            },
+        );
+    }
+
+    /// Insert data whose stream yields an error after `chunks` chunks, to simulate network failures
+    pub fn insert_failing(&mut self, address: &str, data: impl Into<Bytes>, chunks: usize) {
+        self.entries.insert(
+            address.to_string(),
+            MemoryEntry {
+                data: data.into(),
+                fail_after_chunks: Some(chunks),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+impl DataSource for MemorySource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let entry = self
+                .entries
+                .get(address)
+                .ok_or_else(|| format!("No data found at address: {address}"))?;
+
+            let data = entry.data.clone();
+            let data_size = data.len();
+            let chunk_size = self.chunk_size;
+            let chunk_count = data_size.div_ceil(chunk_size);
+            let fail_after = entry.fail_after_chunks;
+
+            let chunks = (0..chunk_count).map(move |i| {
+                if fail_after.is_some_and(|n| i >= n) {
+                    return Err(format!("Simulated failure fetching chunk {i}"));
+                }
+                let start = i * chunk_size;
+                let end = (start + chunk_size).min(data_size);
+                Ok(data.slice(start..end))
+            });
+
+            // Stop after the first error like a real DataStream would
+            let mut failed = false;
+            let chunks = chunks.take_while(move |chunk| {
+                let keep = !failed;
+                failed |= chunk.is_err();
+                keep
+            });
+
+            Ok(ChunkStream::new(data_size, chunks))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            let entry = self
+                .entries
+                .get(address)
+                .ok_or_else(|| format!("No data found at address: {address}"))?;
+            Ok(Box::new(MemoryRangeReader(entry.data.clone())) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+/// Random access over data already in memory
+pub struct MemoryRangeReader(Bytes);
+
+impl MemoryRangeReader {
+    pub fn new(data: Bytes) -> Self {
+        Self(data)
+    }
+}
+
+impl RangeReader for MemoryRangeReader {
+    fn data_size(&self) -> usize {
+        self.0.len()
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        let end = start.saturating_add(len).min(self.0.len());
+        if start > end {
+            return Err(format!("Range start {start} is past the end of the data"));
+        }
+        Ok(self.0.slice(start..end))
+    }
+}
+
+/// Serves local files, addresses are paths relative to `root` (or absolute)
+#[cfg(test)]
+pub struct FileSource {
+    root: PathBuf,
+    chunk_size: usize,
+}
+
+#[cfg(test)]
+impl FileSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    fn resolve(&self, address: &str) -> PathBuf {
+        let path = Path::new(address);
+        if path.is_absolute() {
+            path.to_path_buf()
+        } else {
+            self.root.join(path)
+        }
+    }
+}
+
+#[cfg(test)]
+impl DataSource for FileSource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let path = self.resolve(address);
+            let file = File::open(&path)
+                .map_err(|e| format!("Failed to open file {}: {e}", path.display()))?;
+            let data_size = file
+                .metadata()
+                .map_err(|e| format!("Failed to read metadata of {}: {e}", path.display()))?
+                .len() as usize;
+
+            let chunks = FileChunks {
+                file,
+                chunk_size: self.chunk_size,
+                done: false,
+            };
+            Ok(ChunkStream::new(data_size, chunks))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            let path = self.resolve(address);
+            let file = File::open(&path)
+                .map_err(|e| format!("Failed to open file {}: {e}", path.display()))?;
+            let data_size = file
+                .metadata()
+                .map_err(|e| format!("Failed to read metadata of {}: {e}", path.display()))?
+                .len() as usize;
+            Ok(Box::new(FileRangeReader { file, data_size }) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+#[cfg(test)]
+struct FileRangeReader {
+    file: File,
+    data_size: usize,
+}
+
+#[cfg(test)]
+impl RangeReader for FileRangeReader {
+    fn data_size(&self) -> usize {
+        self.data_size
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        self.file
+            .seek(SeekFrom::Start(start as u64))
+            .map_err(|e| format!("Failed to seek file: {e}"))?;
+
+        let mut buf = Vec::with_capacity(len.min(self.data_size.saturating_sub(start)));
+        (&mut self.file)
+            .take(len as u64)
+            .read_to_end(&mut buf)
+            .map_err(|e| format!("Failed to read file: {e}"))?;
+        Ok(Bytes::from(buf))
+    }
+}
+
+#[cfg(test)]
+struct FileChunks {
+    file: File,
+    chunk_size: usize,
+    done: bool,
+}
+
+#[cfg(test)]
+impl Iterator for FileChunks {
+    type Item = Result<Bytes, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let mut buf = Vec::with_capacity(self.chunk_size);
+        match (&mut self.file)
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut buf)
+        {
+            Ok(0) => {
+                self.done = true;
+                None
+            }
+            Ok(_) => Some(Ok(Bytes::from(buf))),
+            Err(e) => {
+                self.done = true;
+                Some(Err(format!("Failed to read file: {e}")))
+            }
+        }
+    }
+}
+
+/// Serves the data of every address from `offset` on, as if it started there
+pub struct OffsetSource {
+    inner: Arc<dyn DataSource>,
+    offset: usize,
+}
+
+impl OffsetSource {
+    pub fn new(inner: Arc<dyn DataSource>, offset: usize) -> Self {
+        Self { inner, offset }
+    }
+}
+
+impl DataSource for OffsetSource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let reader = self.open_ranged(address).await?;
+            let data_size = reader.data_size();
+            Ok(ChunkStream::new(
+                data_size,
+                RangeChunks::new(reader, 0, DEFAULT_CHUNK_SIZE),
+            ))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            let reader = self.inner.open_ranged(address).await?;
+            let offset = self.offset.min(reader.data_size());
+            Ok(Box::new(OffsetRangeReader { reader, offset }) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+/// Serves an address that was already sniffed without opening it again, other addresses from the inner source
+pub struct PrefetchedSource {
+    inner: Arc<dyn DataSource>,
+    address: String,
+    /// Small data, served from memory to every open
+    data: Option<Bytes>,
+    /// The reader large data was sized through, handed to the first range open
+    reader: Mutex<Option<Box<dyn RangeReader>>>,
+}
+
+impl PrefetchedSource {
+    pub fn new(inner: Arc<dyn DataSource>, address: &str, sniffed: Sniffed) -> Self {
+        let (data, reader) = match sniffed {
+            Sniffed::Small(data) => (Some(data), None),
+            Sniffed::Large(reader) => (None, Some(reader)),
+        };
+        Self {
+            inner,
+            address: address.to_string(),
+            data,
+            reader: Mutex::new(reader),
+        }
+    }
+
+    fn prefetched_reader(&self, address: &str) -> Option<Box<dyn RangeReader>> {
+        if address != self.address {
+            return None;
+        }
+        match &self.data {
+            Some(data) => Some(Box::new(MemoryRangeReader::new(data.clone()))),
+            None => self.reader.lock().unwrap().take(),
+        }
+    }
+}
+
+impl DataSource for PrefetchedSource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            match &self.data {
+                Some(data) if address == self.address => {
+                    let reader = Box::new(MemoryRangeReader::new(data.clone()));
+                    Ok(ChunkStream::new(
+                        data.len(),
+                        RangeChunks::new(reader, 0, DEFAULT_CHUNK_SIZE),
+                    ))
+                }
+                _ => self.inner.open(address).await,
+            }
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            match self.prefetched_reader(address) {
+                Some(reader) => Ok(reader),
+                None => self.inner.open_ranged(address).await,
+            }
+        })
+    }
+}
+
+struct OffsetRangeReader {
+    reader: Box<dyn RangeReader>,
+    offset: usize,
+}
+
+impl RangeReader for OffsetRangeReader {
+    fn data_size(&self) -> usize {
+        self.reader.data_size() - self.offset
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        self.reader.read_range(self.offset + start, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_memory_source_chunks() {
+        let mut source = MemorySource::new(4);
+        source.insert("abc", vec![1u8; 10]);
+
+        let stream = source.open("abc").await.expect("Failed to open");
+        assert_eq!(stream.data_size(), 10);
+
+        let sizes: Vec<usize> = stream.map(|c| c.unwrap().len()).collect();
+        assert_eq!(sizes, vec![4, 4, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_source_failure() {
+        let mut source = MemorySource::new(4);
+        source.insert_failing("abc", vec![1u8; 16], 2);
+
+        let results: Vec<_> = source.open("abc").await.unwrap().collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[2].is_err());
+
+        assert!(source.open("missing").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_source() {
+        let dir = std::env::temp_dir().join("antube_file_source_test");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("video.bin"), vec![7u8; DEFAULT_CHUNK_SIZE + 5]).unwrap();
+
+        let source = FileSource::new(&dir);
+        let stream = source.open("video.bin").await.expect("Failed to open");
+        assert_eq!(stream.data_size(), DEFAULT_CHUNK_SIZE + 5);
+
+        let total: usize = stream.map(|c| c.unwrap().len()).sum();
+        assert_eq!(total, DEFAULT_CHUNK_SIZE + 5);
+
+        let mut reader = source.open_ranged("video.bin").await.unwrap();
+        assert_eq!(reader.read_range(DEFAULT_CHUNK_SIZE, 100).unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_range_chunks_resume() {
+        let mut source = MemorySource::new(4);
+        source.insert("abc", (0u8..10).collect::<Vec<_>>());
+
+        let reader = source.open_ranged("abc").await.unwrap();
+        let chunks: Vec<_> = RangeChunks::new(reader, 3, 4).map(Result::unwrap).collect();
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(&chunks[0][..], &[3, 4, 5, 6]);
+        assert_eq!(&chunks[1][..], &[7, 8, 9]);
+    }
+
+    #[tokio::test]
+    async fn test_memory_range_reader() {
+        let mut source = MemorySource::new(4);
+        source.insert("abc", (0u8..10).collect::<Vec<_>>());
+
+        let mut reader = source.open_ranged("abc").await.unwrap();
+        assert_eq!(reader.data_size(), 10);
+        assert_eq!(&reader.read_range(3, 4).unwrap()[..], &[3, 4, 5, 6]);
+        assert_eq!(&reader.read_range(8, 4).unwrap()[..], &[8, 9]);
+        assert!(reader.read_range(11, 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_readers() {
+        let mut source = MemorySource::new(4);
+        source.insert("abc", (0u8..10).collect::<Vec<_>>());
+
+        let reader = BlockingRangeReader::new(source.open_ranged("abc").await.unwrap());
+        assert_eq!(&reader.read_range(3, 4).await.unwrap()[..], &[3, 4, 5, 6]);
+
+        let chunks = BlockingChunks::new(source.open("abc").await.unwrap());
+        let mut sizes = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            sizes.push(chunk.unwrap().len());
+        }
+        assert_eq!(sizes, vec![4, 4, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_offset_source() {
+        let mut source = MemorySource::new(4);
+        source.insert("abc", (0u8..10).collect::<Vec<_>>());
+        let source = OffsetSource::new(Arc::new(source), 6);
+
+        let stream = source.open("abc").await.unwrap();
+        assert_eq!(stream.data_size(), 4);
+        let data: Vec<u8> = stream.flat_map(|chunk| chunk.unwrap().to_vec()).collect();
+        assert_eq!(data, [6, 7, 8, 9]);
+    }
+
+    #[tokio::test]
+    async fn test_prefetched_source() {
+        let mut source = MemorySource::new(4);
+        source.insert("small", vec![1u8; 3]);
+        source.insert("large", vec![2u8; 10]);
+        let source: Arc<dyn DataSource> = Arc::new(source);
+
+        // Small data is read whole and served from memory afterwards
+        let Sniffed::Small(data) = sniff(source.as_ref(), "small", 5).await.unwrap() else {
+            panic!("small data wasn't read whole");
+        };
+        let prefetched = PrefetchedSource::new(source.clone(), "small", Sniffed::Small(data));
+        assert_eq!(
+            prefetched.open_ranged("small").await.unwrap().data_size(),
+            3
+        );
+        assert_eq!(prefetched.open("small").await.unwrap().data_size(), 3);
+
+        // Large data hands out the reader it was sized through
+        let sniffed = sniff(source.as_ref(), "large", 5).await.unwrap();
+        assert!(matches!(sniffed, Sniffed::Large(_)));
+        let prefetched = PrefetchedSource::new(source, "large", sniffed);
+        let mut reader = prefetched.open_ranged("large").await.unwrap();
+        assert_eq!(&reader.read_range(8, 4).unwrap()[..], &[2, 2]);
+        assert!(prefetched.open_ranged("missing").await.is_err());
+    }
+}
@@ -0,0 +1,462 @@
+use crate::container;
+use crate::data_source::{BlockingRangeReader, DataSource, SourceProvider, DEFAULT_CHUNK_SIZE};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+/// Port the gateway listens on unless configured otherwise
+pub const DEFAULT_GATEWAY_PORT: u16 = 8090;
+
+/// Requests with a longer head are rejected
+const MAX_REQUEST_HEAD_SIZE: usize = 16 * 1024;
+
+/// Readers kept open between requests, the least recently used is dropped beyond this
+const MAX_OPEN_READERS: usize = 16;
+
+/// Data opened by an earlier request, players send many range requests for the same data
+struct OpenData {
+    /// Source the reader was opened through, another one means the network was reset
+    source: Arc<dyn DataSource>,
+    reader: BlockingRangeReader,
+    content_type: &'static str,
+    last_used: Instant,
+}
+
+/// Open data by `network/address`
+type OpenReaders = Arc<Mutex<HashMap<String, OpenData>>>;
+
+/// Local HTTP server answering `GET /<network>/<address>` with the data, for external players
+pub struct Gateway {
+    address: SocketAddr,
+    task: JoinHandle<()>,
+}
+
+impl Gateway {
+    /// Listen on `bind` and serve requests in the background until stopped
+    pub fn start(bind: SocketAddr, sources: SourceProvider) -> Result<Self, String> {
+        let listener = std::net::TcpListener::bind(bind)
+            .and_then(|listener| listener.set_nonblocking(true).map(|()| listener))
+            .and_then(TcpListener::from_std)
+            .map_err(|e| format!("Failed to listen on {bind}: {e}"))?;
+        let address = listener
+            .local_addr()
+            .map_err(|e| format!("Failed to get the gateway address: {e}"))?;
+        println!("Gateway listening on http://{address}");
+
+        let readers: OpenReaders = Arc::default();
+        let task = tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        println!("Gateway failed to accept a connection: {e}");
+                        continue;
+                    }
+                };
+                let sources = sources.clone();
+                let readers = readers.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = handle_connection(stream, sources, readers).await {
+                        println!("Gateway request failed: {e}");
+                    }
+                });
+            }
+        });
+
+        Ok(Self { address, task })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// URL external players can open the data at `address` on `network` from
+    pub fn url(&self, network: &str, address: &str) -> String {
+        format!("http://{}/{network}/{address}", self.address)
+    }
+}
+
+impl Drop for Gateway {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    range: Option<String>,
+}
+
+/// Serve a single request, the connection is closed after the response
+async fn handle_connection(
+    mut stream: TcpStream,
+    sources: SourceProvider,
+    readers: OpenReaders,
+) -> Result<(), String> {
+    let request = match read_request(&mut stream).await {
+        Ok(request) => request,
+        Err(e) => return respond_error(&mut stream, "400 Bad Request", &e).await,
+    };
+    let stream = &mut stream;
+
+    if request.method != "GET" && request.method != "HEAD" {
+        return respond_error(
+            stream,
+            "405 Method Not Allowed",
+            "Only GET and HEAD are supported",
+        )
+        .await;
+    }
+    let Some((network, address)) = request
+        .path
+        .trim_start_matches('/')
+        .split_once('/')
+        .filter(|(network, address)| !network.is_empty() && !address.is_empty())
+    else {
+        return respond_error(stream, "404 Not Found", "Expected /<network>/<address>").await;
+    };
+
+    let source = match sources(network.to_string()).await {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            let error = format!("Unknown network {network:?}");
+            return respond_error(stream, "404 Not Found", &error).await;
+        }
+        Err(e) => return respond_error(stream, "502 Bad Gateway", &e).await,
+    };
+
+    let key = format!("{network}/{address}");
+    let cached = readers
+        .lock()
+        .unwrap()
+        .get_mut(&key)
+        .filter(|open| Arc::ptr_eq(&open.source, &source))
+        .map(|open| {
+            open.last_used = Instant::now();
+            (open.reader.clone(), open.content_type)
+        });
+    let (reader, content_type) = match cached {
+        Some(cached) => cached,
+        None => {
+            let reader = match source.open_ranged(address).await {
+                Ok(reader) => BlockingRangeReader::new(reader),
+                Err(e) => return respond_error(stream, "404 Not Found", &e).await,
+            };
+            let head = match reader
+                .read_range(0, reader.data_size().min(container::SNIFF_SIZE))
+                .await
+            {
+                Ok(head) => head,
+                Err(e) => return respond_error(stream, "502 Bad Gateway", &e).await,
+            };
+            let content_type =
+                container::sniff(&head).map_or("application/octet-stream", |c| c.mime_type());
+            keep_open(&readers, network, key, source, reader.clone(), content_type);
+            (reader, content_type)
+        }
+    };
+    let data_size = reader.data_size();
+
+    let (partial, start, end) = match request.range.as_deref().map(|r| parse_range(r, data_size)) {
+        None | Some(Ok(None)) => (false, 0, data_size),
+        Some(Ok(Some((start, end)))) => (true, start, end),
+        Some(Err(())) => {
+            let head = format!(
+                "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{data_size}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            );
+            return write(stream, head.as_bytes()).await;
+        }
+    };
+    println!(
+        "Gateway {} {network}/{address} bytes {start}-{end} of {data_size}",
+        request.method
+    );
+
+    let status = if partial {
+        "206 Partial Content"
+    } else {
+        "200 OK"
+    };
+    let mut head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nAccept-Ranges: bytes\r\nConnection: close\r\n",
+        end - start
+    );
+    // A 206 always names its range, even when it covers all the data
+    if partial {
+        head.push_str(&format!(
+            "Content-Range: bytes {start}-{}/{data_size}\r\n",
+            end - 1
+        ));
+    }
+    head.push_str("\r\n");
+    write(stream, head.as_bytes()).await?;
+
+    if request.method == "GET" {
+        write_body(stream, &reader, start, end).await?;
+    }
+    Ok(())
+}
+
+/// Keep `reader` for the next requests, dropping the readers opened before `network` was reset
+fn keep_open(
+    readers: &OpenReaders,
+    network: &str,
+    key: String,
+    source: Arc<dyn DataSource>,
+    reader: BlockingRangeReader,
+    content_type: &'static str,
+) {
+    let mut readers = readers.lock().unwrap();
+    let prefix = format!("{network}/");
+    readers.retain(|key, open| !key.starts_with(&prefix) || Arc::ptr_eq(&open.source, &source));
+    if readers.len() >= MAX_OPEN_READERS && !readers.contains_key(&key) {
+        let oldest = readers
+            .iter()
+            .min_by_key(|(_, open)| open.last_used)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            readers.remove(&oldest);
+        }
+    }
+    readers.insert(
+        key,
+        OpenData {
+            source,
+            reader,
+            content_type,
+            last_used: Instant::now(),
+        },
+    );
+}
+
+async fn read_request(stream: &mut TcpStream) -> Result<Request, String> {
+    // Reading stops at the limit, even inside a line that never ends
+    let mut head = BufReader::new((&mut *stream).take(MAX_REQUEST_HEAD_SIZE as u64));
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = head
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("Failed to read request: {e}"))?;
+        if read == 0 {
+            return Err("Incomplete or oversized request".to_string());
+        }
+        let line = line.trim_end().to_string();
+        if line.is_empty() {
+            break;
+        }
+        lines.push(line);
+    }
+
+    let mut request_line = lines
+        .first()
+        .map(|line| line.split(' '))
+        .into_iter()
+        .flatten();
+    let (Some(method), Some(path)) = (request_line.next(), request_line.next()) else {
+        return Err("Invalid request line".to_string());
+    };
+    let range = lines[1..].iter().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        name.eq_ignore_ascii_case("range")
+            .then(|| value.trim().to_string())
+    });
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.split('?').next().unwrap_or_default().to_string(),
+        range,
+    })
+}
+
+/// Byte range `[start, end)` requested by a `Range` header, `None` to serve everything.
+///
+/// Multiple ranges aren't supported and get the whole data, as HTTP allows.
+fn parse_range(header: &str, data_size: usize) -> Result<Option<(usize, usize)>, ()> {
+    let Some(spec) = header.strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let (first, last) = spec.split_once('-').ok_or(())?;
+    let (first, last) = (first.trim(), last.trim());
+
+    let (start, end) = if first.is_empty() {
+        // Suffix range: the last N bytes
+        let suffix: usize = last.parse().map_err(|_| ())?;
+        (data_size.saturating_sub(suffix), data_size)
+    } else {
+        let start: usize = first.parse().map_err(|_| ())?;
+        let end = match last {
+            "" => data_size,
+            last => last.parse::<usize>().map_err(|_| ())?.saturating_add(1),
+        };
+        (start, end.min(data_size))
+    };
+
+    if start >= end {
+        return Err(());
+    }
+    Ok(Some((start, end)))
+}
+
+async fn write_body(
+    stream: &mut TcpStream,
+    reader: &BlockingRangeReader,
+    start: usize,
+    end: usize,
+) -> Result<(), String> {
+    let mut position = start;
+    while position < end {
+        let chunk = reader
+            .read_range(position, DEFAULT_CHUNK_SIZE.min(end - position))
+            .await?;
+        if chunk.is_empty() {
+            return Err(format!("Data ended at {position} of {end} bytes"));
+        }
+        position += chunk.len();
+        // Players close the connection once they have what they need
+        write(stream, &chunk).await?;
+    }
+    Ok(())
+}
+
+async fn respond_error(stream: &mut TcpStream, status: &str, message: &str) -> Result<(), String> {
+    println!("Gateway responding {status}: {message}");
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{message}",
+        message.len()
+    );
+    write(stream, response.as_bytes()).await
+}
+
+async fn write(stream: &mut TcpStream, data: &[u8]) -> Result<(), String> {
+    stream
+        .write_all(data)
+        .await
+        .map_err(|e| format!("Failed to write response: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::{MemoryRangeReader, MemorySource};
+    use tokio::io::AsyncReadExt;
+
+    async fn get(gateway: &Gateway, path: &str, range: Option<&str>) -> String {
+        let mut stream = TcpStream::connect(gateway.address()).await.unwrap();
+        let range = range.map_or(String::new(), |range| format!("Range: {range}\r\n"));
+        let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n{range}\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        String::from_utf8_lossy(&response).to_string()
+    }
+
+    #[tokio::test]
+    async fn test_gateway_ranges() {
+        let mut video = b"\0\0\0\x18ftypmp42".to_vec();
+        video.resize(100, 7);
+        let mut source = MemorySource::new(16);
+        source.insert("video", video);
+        let source: Arc<dyn DataSource> = Arc::new(source);
+
+        let sources: SourceProvider = Arc::new(move |network| {
+            let source = source.clone();
+            Box::pin(async move {
+                match network.as_str() {
+                    "local" => Ok(Some(source)),
+                    "offline" => Err("Failed to connect".to_string()),
+                    _ => Ok(None),
+                }
+            })
+        });
+        let gateway = Gateway::start("127.0.0.1:0".parse().unwrap(), sources).unwrap();
+
+        let response = get(&gateway, "/local/video", None).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("Content-Type: video/mp4"));
+        assert!(response.contains("Content-Length: 100"));
+
+        let response = get(&gateway, "/local/video", Some("bytes=90-")).await;
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content"));
+        assert!(response.contains("Content-Range: bytes 90-99/100"));
+        assert!(response.ends_with("\r\n\r\n\x07\x07\x07\x07\x07\x07\x07\x07\x07\x07"));
+
+        let response = get(&gateway, "/local/video", Some("bytes=0-")).await;
+        assert!(response.starts_with("HTTP/1.1 206 Partial Content"));
+        assert!(response.contains("Content-Range: bytes 0-99/100"));
+        assert!(response.contains("Content-Length: 100"));
+
+        let response = get(&gateway, "/local/video", Some("bytes=100-")).await;
+        assert!(response.starts_with("HTTP/1.1 416"));
+        assert!(get(&gateway, "/moon/video", None)
+            .await
+            .starts_with("HTTP/1.1 404"));
+        assert!(get(&gateway, "/offline/video", None)
+            .await
+            .starts_with("HTTP/1.1 502"));
+        assert!(get(&gateway, "/local/missing", None)
+            .await
+            .starts_with("HTTP/1.1 404"));
+
+        // A head that never ends is cut off at the limit
+        let mut stream = TcpStream::connect(gateway.address()).await.unwrap();
+        stream
+            .write_all(&vec![b'a'; MAX_REQUEST_HEAD_SIZE])
+            .await
+            .unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).await.unwrap();
+        assert!(response.starts_with(b"HTTP/1.1 400"));
+
+        assert_eq!(parse_range("bytes=-10", 100), Ok(Some((90, 100))));
+        assert_eq!(parse_range("bytes=0-0", 100), Ok(Some((0, 1))));
+        assert_eq!(parse_range("bytes=0-9,20-29", 100), Ok(None));
+    }
+
+    #[test]
+    fn test_keep_open_drops_reset_networks() {
+        let readers = OpenReaders::default();
+        let keep = |key: &str, source: &Arc<dyn DataSource>| {
+            let reader = BlockingRangeReader::new(Box::new(MemoryRangeReader::new(
+                bytes::Bytes::from_static(b"data"),
+            )));
+            let network = key.split('/').next().unwrap();
+            keep_open(
+                &readers,
+                network,
+                key.to_string(),
+                source.clone(),
+                reader,
+                "video/mp4",
+            );
+        };
+        let first: Arc<dyn DataSource> = Arc::new(MemorySource::new(4));
+        let reset: Arc<dyn DataSource> = Arc::new(MemorySource::new(4));
+
+        keep("local/a", &first);
+        keep("alpha/a", &first);
+        keep("local/b", &reset);
+        let mut keys: Vec<_> = readers.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        assert_eq!(keys, ["alpha/a", "local/b"]);
+
+        // The least recently used reader makes room
+        for i in 0..MAX_OPEN_READERS {
+            keep(&format!("local/{i}"), &reset);
+        }
+        let readers = readers.lock().unwrap();
+        assert_eq!(readers.len(), MAX_OPEN_READERS);
+        assert!(!readers.contains_key("alpha/a"));
+    }
+}
@@ -0,0 +1,188 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Saved positions closer than this to the start aren't worth resuming
+const MIN_RESUME_SECONDS: f64 = 5.0;
+
+/// Oldest entries are dropped past this many
+const MAX_HISTORY_ENTRIES: usize = 200;
+
+/// A video the user watched and how far they got
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub address: String,
+    pub environment: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Last playback position in seconds
+    pub position: f64,
+    #[serde(default)]
+    pub duration: Option<f64>,
+    /// Byte offset of `position`, for resuming pipelines that can't seek
+    #[serde(default)]
+    pub offset: Option<usize>,
+    pub completed: bool,
+    /// Unix time of the last update, in seconds
+    pub watched_at: u64,
+}
+
+impl HistoryEntry {
+    /// Position to resume from, `None` if the video was finished or barely started
+    pub fn resume_position(&self) -> Option<f64> {
+        (!self.completed && self.position >= MIN_RESUME_SECONDS).then_some(self.position)
+    }
+}
+
+/// Watched videos, most recent first, stored as JSON in the AnTube data directory
+pub struct WatchHistory {
+    path: PathBuf,
+    entries: Vec<HistoryEntry>,
+    /// Changed since the last save
+    dirty: bool,
+}
+
+impl WatchHistory {
+    pub fn open_default() -> Result<Self, String> {
+        let path = dirs_next::data_dir()
+            .ok_or_else(|| "Could not find the data directory".to_string())?
+            .join("antube")
+            .join("history.json");
+        Self::open(path)
+    }
+
+    /// Load the history at `path`, starting empty if the file doesn't exist yet
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, String> {
+        let path = path.into();
+        let entries = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| format!("Invalid history file {}: {e}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(format!("Failed to read {}: {e}", path.display())),
+        };
+        Ok(Self {
+            path,
+            entries,
+            dirty: false,
+        })
+    }
+
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, address: &str, environment: &str) -> Option<&HistoryEntry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.address == address && entry.environment == environment)
+    }
+
+    pub fn record_position(
+        &mut self,
+        address: &str,
+        environment: &str,
+        position: f64,
+        duration: Option<f64>,
+        offset: Option<usize>,
+    ) {
+        let entry = self.touch(address, environment);
+        entry.position = position;
+        entry.duration = duration.or(entry.duration);
+        entry.offset = offset;
+        entry.completed = false;
+    }
+
+    pub fn set_title(&mut self, address: &str, environment: &str, title: &str) {
+        let entry = self.touch(address, environment);
+        entry.title = Some(title.to_string());
+    }
+
+    pub fn mark_completed(&mut self, address: &str, environment: &str) {
+        self.touch(address, environment).completed = true;
+    }
+
+    pub fn remove(&mut self, address: &str, environment: &str) {
+        self.entries
+            .retain(|entry| entry.address != address || entry.environment != environment);
+        self.dirty = true;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.dirty = true;
+    }
+
+    /// Write the history to disk if it changed since it was last saved
+    pub fn save(&mut self) -> Result<(), String> {
+        if !self.dirty {
+            return Ok(());
+        }
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+        }
+        let data = serde_json::to_vec_pretty(&self.entries)
+            .map_err(|e| format!("Failed to serialize history: {e}"))?;
+        std::fs::write(&self.path, data)
+            .map_err(|e| format!("Failed to write {}: {e}", self.path.display()))?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Entry of a video moved to the front, created if it wasn't watched before
+    fn touch(&mut self, address: &str, environment: &str) -> &mut HistoryEntry {
+        let existing = self
+            .entries
+            .iter()
+            .position(|entry| entry.address == address && entry.environment == environment);
+        let mut entry = match existing {
+            Some(index) => self.entries.remove(index),
+            None => HistoryEntry {
+                address: address.to_string(),
+                environment: environment.to_string(),
+                title: None,
+                position: 0.0,
+                duration: None,
+                offset: None,
+                completed: false,
+                watched_at: 0,
+            },
+        };
+        entry.watched_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        self.entries.insert(0, entry);
+        self.entries.truncate(MAX_HISTORY_ENTRIES);
+        self.dirty = true;
+        &mut self.entries[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history_resume_position() {
+        let path = std::env::temp_dir().join("antube_history_test.json");
+        let _ = std::fs::remove_file(&path);
+
+        let mut history = WatchHistory::open(&path).unwrap();
+        history.record_position("a", "local", 42.0, Some(100.0), Some(4200));
+        history.record_position("b", "local", 2.0, None, None);
+        history.set_title("a", "local", "Cats");
+        history.save().unwrap();
+
+        let mut history = WatchHistory::open(&path).unwrap();
+        let entry = history.find("a", "local").unwrap();
+        assert_eq!(history.entries()[0].address, "a");
+        assert_eq!(entry.title.as_deref(), Some("Cats"));
+        assert_eq!(entry.resume_position(), Some(42.0));
+        assert_eq!(entry.offset, Some(4200));
+
+        // Barely started or finished videos play from the start
+        assert_eq!(history.find("b", "local").unwrap().resume_position(), None);
+        history.mark_completed("a", "local");
+        assert_eq!(history.find("a", "local").unwrap().resume_position(), None);
+    }
+}
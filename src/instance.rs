@@ -0,0 +1,107 @@
+#[cfg(unix)]
+use std::io::Write;
+#[cfg(unix)]
+use std::path::PathBuf;
+
+/// Socket the running instance listens on, in the user's runtime directory or a directory of their own.
+/// Handoffs may carry private data maps, so other users must not reach it.
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    let dir = dirs_next::runtime_dir()
+        .or_else(|| dirs_next::cache_dir().map(|dir| dir.join("antube")))
+        .unwrap_or_else(|| {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("antube-{user}"))
+        });
+    dir.join("antube.sock")
+}
+
+/// Create the directory of the socket if needed and make sure only the user can enter it
+#[cfg(unix)]
+fn make_private_dir(path: &std::path::Path) -> Result<(), String> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let Some(dir) = path.parent() else {
+        return Ok(());
+    };
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .and_then(|()| std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700)))
+        .map_err(|e| format!("Failed to make {} private: {e}", dir.display()))
+}
+
+/// Send `uri` to an already running instance over its socket, false if there is none to take it
+#[cfg(unix)]
+pub fn hand_off(uri: &str) -> Result<bool, String> {
+    let path = socket_path();
+    let mut stream = match std::os::unix::net::UnixStream::connect(&path) {
+        Ok(stream) => stream,
+        // No socket, or a stale one left by an instance that didn't exit cleanly
+        Err(_) => return Ok(false),
+    };
+    // Not echoed in the error, the URI may be a private data map
+    writeln!(stream, "{uri}").map_err(|e| format!("Failed to hand off to AnTube: {e}"))?;
+    Ok(true)
+}
+
+#[cfg(not(unix))]
+pub fn hand_off(_uri: &str) -> Result<bool, String> {
+    Ok(false)
+}
+
+/// Accept URIs from later launches, calling `on_uri` with each one
+#[cfg(unix)]
+pub fn listen(on_uri: impl Fn(String) + Send + Sync + 'static) -> Result<(), String> {
+    use std::sync::Arc;
+    use tokio::io::AsyncBufReadExt;
+
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = socket_path();
+    make_private_dir(&path)?;
+    if path.exists() {
+        // Another instance may have started since `hand_off`, its socket must be left alone
+        if std::os::unix::net::UnixStream::connect(&path).is_ok() {
+            return Err(format!("Another AnTube is listening on {}", path.display()));
+        }
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove stale socket {}: {e}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .map_err(|e| format!("Failed to listen on {}: {e}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .map_err(|e| format!("Failed to make {} private: {e}", path.display()))?;
+    println!("Listening for URIs on {}", path.display());
+
+    let on_uri = Arc::new(on_uri);
+    tokio::spawn(async move {
+        loop {
+            let stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    println!("Failed to accept a handoff connection: {e}");
+                    continue;
+                }
+            };
+            // A launch that connects without sending doesn't hold up the others
+            let on_uri = on_uri.clone();
+            tokio::spawn(async move {
+                let mut lines = tokio::io::BufReader::new(stream).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let uri = line.trim();
+                    if !uri.is_empty() {
+                        on_uri(uri.to_string());
+                    }
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn listen(_on_uri: impl Fn(String) + Send + Sync + 'static) -> Result<(), String> {
+    Err("Single instance handoff needs Unix sockets".to_string())
+}
@@ -1,13 +1,53 @@
+mod abr;
+mod autonomi_src;
+mod cache;
+mod channel;
+mod cli;
+mod config;
+mod container;
+mod data_map;
+mod data_source;
+mod gateway;
+mod history;
+mod instance;
+mod manifest;
+mod metadata;
+mod network;
+mod playlist;
+mod pool;
+mod prebuffer;
+mod retry;
 mod server;
+mod stats;
+mod uri;
 mod video_streamer;
 
-use server::Server;
-use server::{DEFAULT_ENVIRONMENT, ENVIRONMENTS};
-use video_streamer::VideoStreamer;
+use cache::{CacheStats, CachedSource, ChunkCache};
+use channel::{FeedVideo, Subscriptions, FEED_POLL_INTERVAL};
+use config::{Config, PipelineSettings, MAX_DECODEBIN_MAX_SIZE_MS};
+use data_source::{
+    sniff, BlockingChunks, BlockingRangeReader, ChunkStream, DataSource, OffsetSource,
+    PrefetchedSource, RangeChunks, Sniffed, RANGE_CHUNK_SIZE,
+};
+use gateway::Gateway;
+use history::{HistoryEntry, WatchHistory};
+use manifest::{ManifestSource, RenditionChange};
+use metadata::VideoMetadata;
+use playlist::{Playlist, PlaylistItem, Queue};
+use pool::{ConnectionStatus, ServerPool};
+use prebuffer::PrebufferTarget;
+use retry::RetryPolicy;
+use stats::TransferStats;
+use uri::AntubeUri;
+use video_streamer::{PipelineEvent, PipelineState, VideoOutput, VideoStreamer};
 
 use clap::Parser;
 use eframe::egui;
 use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
@@ -15,21 +55,94 @@ use tokio::task::JoinHandle;
 #[command(name = "antube")]
 #[command(about = "AnTube - Autonomi Video Streamer")]
 struct Args {
-    /// Network environment (local, autonomi, alpha)
-    #[arg(short, long, default_value = "autonomi")]
-    network: String,
+    /// Network environment (local, autonomi, alpha or a custom network), overrides the config file
+    #[arg(short, long, global = true)]
+    network: Option<String>,
 
-    /// Data address to stream
+    /// Config file to use instead of config.json in the AnTube config directory
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+
+    /// Data address or antube:// URI to stream
     #[arg(short, long)]
     address: Option<String>,
 
+    /// antube://<network>/<address>?t=<seconds> URI or address to open, in the running AnTube if there is one
+    #[arg(value_name = "URI")]
+    uri: Option<String>,
+
     /// Use default test video (only works with local network)
     #[arg(long)]
     test: bool,
+
+    /// Show video in a separate native window instead of inside the app
+    #[arg(long)]
+    native_video: bool,
+
+    /// Size limit of the on-disk chunk cache in MB
+    #[arg(long)]
+    cache_size_mb: Option<u64>,
+
+    /// Don't cache fetched chunks on disk
+    #[arg(long)]
+    no_cache: bool,
+
+    /// Times a failed chunk fetch is retried before the stream gives up
+    #[arg(long)]
+    max_retries: Option<u32>,
+
+    /// Wait before the first retry in milliseconds, doubled for each further retry
+    #[arg(long)]
+    retry_backoff_ms: Option<u64>,
+
+    /// Seconds of playback to buffer before starting, when the bitrate is known
+    #[arg(long)]
+    prebuffer_seconds: Option<f64>,
+
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+}
+
+impl Args {
+    /// Replace the config file settings given on the command line
+    fn apply_overrides(&self, config: &mut Config) {
+        if let Some(network) = &self.network {
+            config.network = network.clone();
+        }
+        if self.test {
+            config.network = "local".to_string();
+        }
+        config.native_video |= self.native_video;
+        config.cache_enabled &= !self.no_cache;
+        if let Some(cache_size_mb) = self.cache_size_mb {
+            config.cache_size_mb = cache_size_mb;
+        }
+        if let Some(max_retries) = self.max_retries {
+            config.max_retries = max_retries;
+        }
+        if let Some(retry_backoff_ms) = self.retry_backoff_ms {
+            config.retry_backoff_ms = retry_backoff_ms;
+        }
+        if let Some(prebuffer_seconds) = self.prebuffer_seconds {
+            config.prebuffer_seconds = prebuffer_seconds;
+        }
+    }
 }
 
 type StreamId = u32;
 
+/// Largest trailing `moov` fetched ahead to start an MP4 that isn't fast start
+const MAX_TRAILING_MOOV_SIZE: usize = 64 * 1024 * 1024; // 64MB
+
+/// How often the watch history is written to disk while videos play
+const HISTORY_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Data up to this size is read whole to check for playlist, metadata and manifest documents
+const MAX_DOCUMENT_SIZE: usize = 1024 * 1024; // 1MB
+
+/// Name of playlists saved or published before one was loaded
+const DEFAULT_PLAYLIST_NAME: &str = "AnTube playlist";
+
 #[derive(Debug, Clone)]
 struct StreamInfo {
     id: StreamId,
@@ -37,6 +150,18 @@ struct StreamInfo {
     environment: String,
     status: StreamStatus,
     created_at: std::time::Instant,
+    /// Pipeline buffering level while below 100%
+    buffering_percent: Option<i32>,
+    cache_stats: Option<Arc<CacheStats>>,
+    /// Current retry attempt and retry limit while a failed fetch is being retried
+    retrying: Option<(u32, u32)>,
+    prebuffer: Option<PrebufferTarget>,
+    /// Renditions played so far and the segment each one started at
+    renditions: Vec<(String, usize)>,
+    /// Title and description when the address is a metadata document
+    metadata: Option<VideoMetadata>,
+    /// Position in seconds and byte offset the data was started from when resuming by skipping bytes
+    resumed_from: Option<(f64, usize)>,
 }
 
 #[derive(Debug, Clone)]
@@ -45,16 +170,127 @@ enum StreamStatus {
     Streaming {
         total_bytes_received: usize,
         chunks_received: usize,
-        last_update_time: std::time::Instant,
         total_size: usize,
+        paused: bool,
+        stats: TransferStats,
     },
     Completed {
         total_bytes_received: usize,
         chunks_received: usize,
+        paused: bool,
+        stats: TransferStats,
+    },
+    PlaybackFinished {
+        total_bytes_received: usize,
+        chunks_received: usize,
+        stats: TransferStats,
+    },
+    DecodeFailed {
+        message: String,
     },
+    Stopped,
     Error {
         message: String,
     },
+    Uploading {
+        stage: String,
+        total_size: usize,
+    },
+    Uploaded {
+        address: String,
+        cost: String,
+    },
+}
+
+/// Playback control requested from a stream card
+#[derive(Debug, Clone, Copy)]
+enum StreamAction {
+    Pause,
+    Resume,
+    Stop,
+    Seek(f64),
+}
+
+/// Action requested from the queue panel
+#[derive(Debug, Clone, Copy)]
+enum QueueAction {
+    Play(usize),
+    Remove(usize),
+    Next,
+    ToggleShuffle,
+    CycleRepeat,
+    Clear,
+    Open,
+    Save,
+    Publish,
+}
+
+/// Action requested from the watch history window
+#[derive(Debug, Clone)]
+enum HistoryAction {
+    Resume(HistoryEntry),
+    Play(HistoryEntry),
+    Remove(HistoryEntry),
+    Clear,
+}
+
+/// Action requested from the channel feed
+#[derive(Debug, Clone)]
+enum FeedAction {
+    Play {
+        channel: String,
+        video: String,
+        environment: String,
+    },
+    MarkSeen {
+        channel: String,
+    },
+    Unsubscribe {
+        channel: String,
+    },
+}
+
+impl StreamStatus {
+    fn set_paused(&mut self, value: bool) {
+        if let StreamStatus::Streaming { paused, .. } | StreamStatus::Completed { paused, .. } =
+            self
+        {
+            *paused = value;
+        }
+    }
+
+    /// Size of the data being streamed, once known
+    fn data_size(&self) -> Option<usize> {
+        match self {
+            StreamStatus::Streaming { total_size, .. } => Some(*total_size),
+            StreamStatus::Completed {
+                total_bytes_received,
+                ..
+            }
+            | StreamStatus::PlaybackFinished {
+                total_bytes_received,
+                ..
+            } => Some(*total_bytes_received),
+            _ => None,
+        }
+    }
+
+    fn stats_mut(&mut self) -> Option<&mut TransferStats> {
+        match self {
+            StreamStatus::Streaming { stats, .. }
+            | StreamStatus::Completed { stats, .. }
+            | StreamStatus::PlaybackFinished { stats, .. } => Some(stats),
+            _ => None,
+        }
+    }
+
+    fn is_paused(&self) -> bool {
+        matches!(
+            self,
+            StreamStatus::Streaming { paused: true, .. }
+                | StreamStatus::Completed { paused: true, .. }
+        )
+    }
 }
 
 enum StreamEvent {
@@ -68,33 +304,200 @@ enum StreamEvent {
     },
     StreamComplete {
         stream_id: StreamId,
-        video_streamer: VideoStreamer,
     },
     StreamError {
         stream_id: StreamId,
         error: String,
     },
+    PrebufferTarget {
+        stream_id: StreamId,
+        target: PrebufferTarget,
+    },
+    Retrying {
+        stream_id: StreamId,
+        attempt: u32,
+        max_retries: u32,
+        error: String,
+    },
+    RenditionChanged {
+        stream_id: StreamId,
+        change: RenditionChange,
+    },
+    MetadataLoaded {
+        stream_id: StreamId,
+        metadata: VideoMetadata,
+        thumbnail: Option<egui::ColorImage>,
+    },
+    ChannelPolled {
+        channel: String,
+        result: Result<Vec<FeedVideo>, String>,
+    },
+    ChannelPublished {
+        channel: String,
+    },
+    PlaylistLoaded {
+        stream_id: StreamId,
+        playlist: Playlist,
+    },
+    PlaylistPublished {
+        result: Result<String, String>,
+        /// Private items left out of the playlist
+        skipped: usize,
+    },
+    /// URI handed over by another launch of the app
+    OpenUri {
+        uri: String,
+    },
     VideoStreamerReady {
         stream_id: StreamId,
+        video_streamer: Arc<VideoStreamer>,
+    },
+    UploadProgress {
+        stream_id: StreamId,
+        stage: String,
+        total_size: usize,
+    },
+    UploadComplete {
+        stream_id: StreamId,
+        address: String,
+        cost: String,
+    },
+    PipelineError {
+        stream_id: StreamId,
+        error: String,
+    },
+    PlaybackFinished {
+        stream_id: StreamId,
+    },
+    Buffering {
+        stream_id: StreamId,
+        percent: i32,
     },
+    StateChanged {
+        stream_id: StreamId,
+        state: PipelineState,
+    },
+    Underrun {
+        stream_id: StreamId,
+    },
+    Refilled {
+        stream_id: StreamId,
+    },
+}
+
+impl StreamEvent {
+    fn from_pipeline_event(stream_id: StreamId, event: PipelineEvent) -> Self {
+        match event {
+            PipelineEvent::Error { message } => StreamEvent::PipelineError {
+                stream_id,
+                error: message,
+            },
+            PipelineEvent::PlaybackFinished => StreamEvent::PlaybackFinished { stream_id },
+            PipelineEvent::Buffering { percent } => StreamEvent::Buffering { stream_id, percent },
+            PipelineEvent::StateChanged { state } => StreamEvent::StateChanged { stream_id, state },
+            PipelineEvent::Underrun => StreamEvent::Underrun { stream_id },
+            PipelineEvent::Refilled => StreamEvent::Refilled { stream_id },
+        }
+    }
+}
+
+/// Per-stream playback settings, captured when the stream is added
+#[derive(Debug, Clone)]
+struct StreamOptions {
+    video_output: VideoOutput,
+    retry: RetryPolicy,
+    prebuffer_seconds: f64,
+    /// Hold the pipeline paused once prebuffered, for queue items fetched ahead
+    start_paused: bool,
+    /// Skip this many bytes from the start of the data, to resume where a pipeline can't seek
+    start_offset: usize,
+    pipeline: PipelineSettings,
+    /// Set when the user stops the stream, checked between range reads
+    cancelled: Arc<AtomicBool>,
+}
+
+/// How the linear download ended once the pipeline was running
+struct LinearOutcome {
+    video_streamer: Arc<VideoStreamer>,
+    /// Byte offset the pipeline seeked to before the download finished
+    seek_offset: Option<usize>,
+    /// Trailing boxes fetched ahead and their offset, served from memory when the pipeline seeks to them
+    tail: Option<(usize, bytes::Bytes)>,
+    /// Bytes from the start already reported as received, reading them again isn't progress
+    received: usize,
 }
 
 struct AntubeApp {
     address_input: String,
     selected_env: String,
     streams: HashMap<StreamId, StreamInfo>,
-    video_streamers: HashMap<StreamId, VideoStreamer>,
+    video_streamers: HashMap<StreamId, Arc<VideoStreamer>>,
+    video_textures: HashMap<StreamId, egui::TextureHandle>,
+    thumbnail_textures: HashMap<StreamId, egui::TextureHandle>,
+    native_video: bool,
+    /// Publish a metadata document alongside uploaded videos
+    upload_metadata: bool,
+    /// Add uploaded videos to the user's channel
+    upload_to_channel: bool,
+    /// The user's own channel once something was published to it
+    my_channel: Option<String>,
+    subscriptions: Option<Subscriptions>,
+    /// Latest videos of each subscribed channel
+    feed: HashMap<String, Result<Vec<FeedVideo>, String>>,
+    last_feed_poll: Option<std::time::Instant>,
+    queue: Queue,
+    playlist_name: String,
+    /// Stream of the queue item playing
+    queue_stream: Option<StreamId>,
+    /// Next queue item and its stream, started paused ahead of time
+    queue_prefetch: Option<(usize, StreamId)>,
+    /// Outcome of the last playlist load, save or publish
+    queue_status: Option<String>,
+    history: Option<WatchHistory>,
+    last_history_save: std::time::Instant,
+    history_open: bool,
+    /// Streams to seek to a saved position once their pipeline can seek
+    pending_resumes: HashMap<StreamId, HistoryEntry>,
+    /// Streams opened from a URI with a start position, seeked once their pipeline can
+    pending_seeks: HashMap<StreamId, f64>,
+    /// Why the address box couldn't be opened
+    address_error: Option<String>,
     stream_receiver: mpsc::UnboundedReceiver<StreamEvent>,
     stream_sender: mpsc::UnboundedSender<StreamEvent>,
     stream_tasks: HashMap<StreamId, JoinHandle<()>>,
+    /// Cancel flags of the running downloads, whose blocking reads can't be aborted
+    stream_cancels: HashMap<StreamId, Arc<AtomicBool>>,
     next_stream_id: StreamId,
+    chunk_cache: Option<Arc<ChunkCache>>,
+    /// Clients shared by all streams, one per network
+    pool: ServerPool,
+    /// HTTP server handing streams to external players, while enabled
+    gateway: Option<Gateway>,
+    /// Why the gateway couldn't be started
+    gateway_error: Option<String>,
+    /// Outcome of the last Subscribe click, an error to show in red
+    subscribe_status: Option<Result<String, String>>,
+    config: Config,
+    /// Settings of the config file without the command line overrides, what the settings window edits
+    file_config: Config,
+    /// Where the settings window saves the config, `None` without a config directory
+    config_path: Option<PathBuf>,
+    /// Settings being edited, while the settings window is open
+    settings_draft: Option<Config>,
+    /// Config errors found at startup or when saving the settings
+    settings_error: Option<String>,
 }
 
 impl AntubeApp {
-    fn new(mut args: Args) -> Self {
+    fn new(
+        args: Args,
+        config: Config,
+        file_config: Config,
+        config_path: Option<PathBuf>,
+        config_error: Option<String>,
+    ) -> Self {
         // Use test address if --test flag is provided
         let address = if args.test {
-            args.network = "local".to_string();
             "d8949e2bd7bc0f60d6062510b4f98c9fd92a3bd70567ab9e43f79eb9f8aa24e6".to_string()
         } else {
             args.address.unwrap_or_default()
@@ -103,15 +506,85 @@ impl AntubeApp {
         // Create global event channel for all streams
         let (stream_sender, stream_receiver) = mpsc::unbounded_channel();
 
+        // Chunk cache shared by all streams
+        let chunk_cache = if !config.cache_enabled {
+            None
+        } else {
+            match ChunkCache::open_default(config.cache_size_mb) {
+                Ok(cache) => Some(Arc::new(cache)),
+                Err(e) => {
+                    println!("Chunk cache disabled: {e}");
+                    None
+                }
+            }
+        };
+
+        let subscriptions = match Subscriptions::open_default() {
+            Ok(subscriptions) => Some(subscriptions),
+            Err(e) => {
+                println!("Channel subscriptions disabled: {e}");
+                None
+            }
+        };
+
+        let pool = ServerPool::new(config.networks.clone());
+        pool.spawn_health_checks();
+        if let Err(e) = autonomi_src::register(pool.source_provider()) {
+            println!("autonomi:// URIs disabled: {e}");
+        }
+        if config.connect_on_startup {
+            pool.connect_eagerly(&config.network);
+        }
+
+        let history = match WatchHistory::open_default() {
+            Ok(history) => Some(history),
+            Err(e) => {
+                println!("Watch history disabled: {e}");
+                None
+            }
+        };
+
         let mut app = Self {
             address_input: address,
-            selected_env: args.network,
+            selected_env: config.network.clone(),
             streams: HashMap::new(),
             video_streamers: HashMap::new(),
+            video_textures: HashMap::new(),
+            thumbnail_textures: HashMap::new(),
+            native_video: config.native_video,
+            upload_metadata: false,
+            upload_to_channel: false,
+            my_channel: None,
+            subscriptions,
+            feed: HashMap::new(),
+            last_feed_poll: None,
+            queue: Queue::default(),
+            playlist_name: DEFAULT_PLAYLIST_NAME.to_string(),
+            queue_stream: None,
+            queue_prefetch: None,
+            queue_status: None,
+            history,
+            last_history_save: std::time::Instant::now(),
+            history_open: false,
+            pending_resumes: HashMap::new(),
+            pending_seeks: HashMap::new(),
+            address_error: None,
             stream_receiver,
             stream_sender,
             stream_tasks: HashMap::new(),
+            stream_cancels: HashMap::new(),
             next_stream_id: 1,
+            chunk_cache,
+            pool,
+            gateway: None,
+            gateway_error: None,
+            subscribe_status: None,
+            // Invalid config files open the settings window so they can be fixed
+            settings_draft: config_error.as_ref().map(|_| file_config.clone()),
+            settings_error: config_error,
+            config,
+            file_config,
+            config_path,
         };
 
         // Auto-start streaming if address was provided or test flag used
@@ -125,23 +598,41 @@ impl AntubeApp {
 
 impl Default for AntubeApp {
     fn default() -> Self {
-        Self::new(Args {
-            network: DEFAULT_ENVIRONMENT.to_string(),
-            address: None,
-            test: false,
-        })
+        Self::new(
+            Args {
+                network: None,
+                config: None,
+                address: None,
+                uri: None,
+                test: false,
+                native_video: false,
+                cache_size_mb: None,
+                no_cache: false,
+                max_retries: None,
+                retry_backoff_ms: None,
+                prebuffer_seconds: None,
+                command: None,
+            },
+            Config::default(),
+            Config::default(),
+            None,
+            None,
+        )
     }
 }
 
 impl eframe::App for AntubeApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Request periodic repaints while any stream is active
-        let has_active_streams = self.streams.values().any(|stream| {
-            matches!(
-                stream.status,
-                StreamStatus::Connecting | StreamStatus::Streaming { .. }
-            )
-        });
+        // Request periodic repaints while any stream is active or playing
+        let has_active_streams = !self.video_streamers.is_empty()
+            || self.streams.values().any(|stream| {
+                matches!(
+                    stream.status,
+                    StreamStatus::Connecting
+                        | StreamStatus::Streaming { .. }
+                        | StreamStatus::Uploading { .. }
+                )
+            });
         if has_active_streams {
             ctx.request_repaint_after(std::time::Duration::from_millis(100));
         }
@@ -157,61 +648,305 @@ impl eframe::App for AntubeApp {
                         stream.status = StreamStatus::Streaming {
                             total_bytes_received: 0,
                             chunks_received: 0,
-                            last_update_time: std::time::Instant::now(),
                             total_size,
+                            paused: false,
+                            stats: TransferStats::new(std::time::Instant::now()),
                         };
                         println!("Stream {stream_id} connected, total size: {total_size} bytes");
                     }
                 }
                 StreamEvent::ChunkReceived { stream_id, size } => {
                     if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.retrying = None;
                         if let StreamStatus::Streaming {
                             total_bytes_received,
                             chunks_received,
-                            last_update_time,
-                            total_size: _,
+                            stats,
+                            ..
                         } = &mut stream.status
                         {
                             *chunks_received += 1;
                             *total_bytes_received += size;
 
-                            *last_update_time = std::time::Instant::now();
+                            stats.record_chunk(size, std::time::Instant::now());
                         }
                     }
                 }
-                StreamEvent::VideoStreamerReady { stream_id } => {
-                    println!("Stream {stream_id} video streamer ready");
-                }
-                StreamEvent::StreamComplete {
+                StreamEvent::VideoStreamerReady {
                     stream_id,
                     video_streamer,
                 } => {
+                    // A rendition switch may have changed the size since the pipeline was created
+                    if let Some(StreamStatus::Streaming { total_size, .. }) =
+                        self.streams.get(&stream_id).map(|stream| &stream.status)
+                    {
+                        video_streamer.set_size(*total_size);
+                    }
+                    // Store the VideoStreamer to keep it alive and control playback from the UI
+                    self.video_streamers.insert(stream_id, video_streamer);
+                    println!("Stream {stream_id} video streamer ready");
+
+                    if self.queue_stream == Some(stream_id) {
+                        // A prefetched item may have been started paused before its turn came
+                        self.apply_stream_action(stream_id, StreamAction::Resume);
+                    } else if self.queue_prefetch.is_some_and(|(_, id)| id == stream_id) {
+                        if let Some(stream) = self.streams.get_mut(&stream_id) {
+                            stream.status.set_paused(true);
+                        }
+                    }
+                }
+                StreamEvent::StreamComplete { stream_id } => {
                     if let Some(stream) = self.streams.get_mut(&stream_id) {
                         if let StreamStatus::Streaming {
                             total_bytes_received,
                             chunks_received,
+                            paused,
+                            stats,
                             ..
                         } = &stream.status
                         {
                             stream.status = StreamStatus::Completed {
                                 total_bytes_received: *total_bytes_received,
                                 chunks_received: *chunks_received,
+                                paused: *paused,
+                                stats: stats.clone(),
+                            };
+                            println!("Stream {stream_id} completed");
+                        }
+                    }
+                    if self.queue_stream == Some(stream_id) {
+                        self.prefetch_next_queue_item();
+                    }
+                }
+                StreamEvent::UploadProgress {
+                    stream_id,
+                    stage,
+                    total_size,
+                } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.status = StreamStatus::Uploading { stage, total_size };
+                    }
+                }
+                StreamEvent::UploadComplete {
+                    stream_id,
+                    address,
+                    cost,
+                } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.status = StreamStatus::Uploaded {
+                            address: address.clone(),
+                            cost,
+                        };
+                        println!("Upload {stream_id} complete at address {address}");
+                    }
+                    // Put the new address in the address box ready to share or stream
+                    self.address_input = address;
+                }
+                StreamEvent::PipelineError { stream_id, error } => {
+                    // Ignore errors from pipelines torn down by the user
+                    if self.video_streamers.contains_key(&stream_id) {
+                        println!("Stream {stream_id} pipeline error: {error}");
+                        self.teardown_stream(stream_id);
+                        if let Some(stream) = self.streams.get_mut(&stream_id) {
+                            stream.status = StreamStatus::DecodeFailed { message: error };
+                        }
+                        self.skip_failed_queue_stream(stream_id);
+                    }
+                }
+                StreamEvent::PlaybackFinished { stream_id } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        if let StreamStatus::Completed {
+                            total_bytes_received,
+                            chunks_received,
+                            stats,
+                            ..
+                        } = &stream.status
+                        {
+                            stream.status = StreamStatus::PlaybackFinished {
+                                total_bytes_received: *total_bytes_received,
+                                chunks_received: *chunks_received,
+                                stats: stats.clone(),
                             };
-                            // Store the VideoStreamer to keep it alive
-                            self.video_streamers.insert(stream_id, video_streamer);
-                            println!("Stream {stream_id} completed and VideoStreamer stored");
+                            println!("Stream {stream_id} playback finished");
+                        }
+                    }
+                    if let (Some(history), Some(stream)) = (
+                        &mut self.history,
+                        self.streams
+                            .get(&stream_id)
+                            .filter(|stream| !data_map::is_private(&stream.address)),
+                    ) {
+                        history.mark_completed(&stream.address, &stream.environment);
+                    }
+                    self.save_history();
+                    if self.queue_stream == Some(stream_id) {
+                        self.advance_queue();
+                    }
+                }
+                StreamEvent::Buffering { stream_id, percent } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.buffering_percent = (percent < 100).then_some(percent);
+                        if let Some(stats) = stream.status.stats_mut() {
+                            let now = std::time::Instant::now();
+                            if percent < 100 {
+                                stats.start_stall(now);
+                            } else {
+                                stats.end_stall(now);
+                            }
+                        }
+                    }
+                }
+                StreamEvent::Underrun { stream_id } => {
+                    if let Some(stats) = self
+                        .streams
+                        .get_mut(&stream_id)
+                        .and_then(|stream| stream.status.stats_mut())
+                    {
+                        stats.start_stall(std::time::Instant::now());
+                    }
+                }
+                StreamEvent::Refilled { stream_id } => {
+                    // A stall the pipeline reports as buffering lasts until it is done buffering
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        if stream.buffering_percent.is_none() {
+                            if let Some(stats) = stream.status.stats_mut() {
+                                stats.end_stall(std::time::Instant::now());
+                            }
+                        }
+                    }
+                }
+                StreamEvent::StateChanged { stream_id, state } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        match state {
+                            PipelineState::Paused => stream.status.set_paused(true),
+                            PipelineState::Playing => {
+                                stream.status.set_paused(false);
+
+                                // The pipeline only reaches playing once the first frame is shown
+                                let after = stream.created_at.elapsed();
+                                if let Some(stats) = stream.status.stats_mut() {
+                                    stats.record_first_frame(after);
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                StreamEvent::PrebufferTarget { stream_id, target } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.prebuffer = Some(target);
+                    }
+                }
+                StreamEvent::Retrying {
+                    stream_id,
+                    attempt,
+                    max_retries,
+                    error,
+                } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.retrying = Some((attempt, max_retries));
+                        println!("Stream {stream_id} retrying ({attempt}/{max_retries}): {error}");
+                    }
+                }
+                StreamEvent::RenditionChanged { stream_id, change } => {
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        let unchanged = stream
+                            .renditions
+                            .last()
+                            .is_some_and(|(name, _)| *name == change.rendition);
+                        if !unchanged {
+                            stream.renditions.push((change.rendition, change.segment));
+                        }
+                        if let StreamStatus::Streaming { total_size, .. } = &mut stream.status {
+                            *total_size = change.data_size;
+                        }
+                    }
+                    if let Some(video_streamer) = self.video_streamers.get(&stream_id) {
+                        video_streamer.set_size(change.data_size);
+                    }
+                }
+                StreamEvent::MetadataLoaded {
+                    stream_id,
+                    metadata,
+                    thumbnail,
+                } => {
+                    if let Some(index) = self.queue_item_of(stream_id) {
+                        self.queue.set_title(index, &metadata.title);
+                    }
+                    if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.metadata = Some(metadata);
+                        if let Some(image) = thumbnail {
+                            let texture = ctx.load_texture(
+                                format!("thumbnail-{stream_id}"),
+                                image,
+                                egui::TextureOptions::LINEAR,
+                            );
+                            self.thumbnail_textures.insert(stream_id, texture);
                         }
                     }
                 }
+                StreamEvent::ChannelPolled { channel, result } => {
+                    if let Err(e) = &result {
+                        println!("Failed to poll channel {channel}: {e}");
+                    }
+                    self.feed.insert(channel, result);
+                }
+                StreamEvent::ChannelPublished { channel } => {
+                    self.my_channel = Some(channel);
+                }
+                StreamEvent::PlaylistLoaded {
+                    stream_id,
+                    playlist,
+                } => {
+                    self.load_playlist(stream_id, playlist);
+                }
+                StreamEvent::PlaylistPublished { result, skipped } => {
+                    self.queue_status = Some(match result {
+                        Ok(address) => {
+                            println!("Playlist published at {address}");
+                            // Put the playlist address in the address box ready to share
+                            self.address_input = address.clone();
+                            format!("Published at {address}{}", private_items_note(skipped))
+                        }
+                        Err(e) => format!("Publish failed: {e}"),
+                    });
+                }
+                StreamEvent::OpenUri { uri } => {
+                    println!("Opening {} from another launch", data_map::redact(&uri));
+                    self.address_input = uri;
+                    self.connect_and_stream();
+                }
                 StreamEvent::StreamError { stream_id, error } => {
                     if let Some(stream) = self.streams.get_mut(&stream_id) {
+                        stream.retrying = None;
                         stream.status = StreamStatus::Error { message: error };
                         println!("Stream {stream_id} error");
                     }
+                    self.skip_failed_queue_stream(stream_id);
                 }
             }
         }
 
+        self.update_video_textures(ctx);
+        self.update_history();
+        self.apply_pending_seeks();
+
+        // Check subscribed channels for new videos
+        let has_subscriptions = self
+            .subscriptions
+            .as_ref()
+            .is_some_and(|subscriptions| !subscriptions.channels().is_empty());
+        if has_subscriptions {
+            let polled_recently = self
+                .last_feed_poll
+                .is_some_and(|polled| polled.elapsed() < FEED_POLL_INTERVAL);
+            if !polled_recently {
+                self.poll_feed();
+            }
+            // Keep picking up poll results while the app is idle
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+
         // Clean up completed streaming tasks
         let mut finished_tasks = Vec::new();
 
@@ -224,29 +959,72 @@ impl eframe::App for AntubeApp {
         for stream_id in finished_tasks {
             println!("Cleaning up finished streaming task {stream_id}");
             self.stream_tasks.remove(&stream_id);
+            self.stream_cancels.remove(&stream_id);
             // Note: Keep VideoStreamer alive even after task finishes - user might still be watching
         }
 
+        // Playback controls clicked on stream cards, applied once the UI is drawn
+        let mut stream_actions = Vec::new();
+        let mut feed_actions = Vec::new();
+        let mut queue_actions = Vec::new();
+        let mut history_actions = Vec::new();
+
+        let mut history_open = self.history_open;
+        egui::Window::new("Watch history")
+            .open(&mut history_open)
+            .default_width(420.0)
+            .show(ctx, |ui| self.show_history(ui, &mut history_actions));
+        self.history_open = history_open;
+
+        self.show_settings(ctx);
+
+        if !self.queue.is_empty() || self.queue_status.is_some() {
+            egui::SidePanel::left("queue")
+                .default_width(240.0)
+                .show(ctx, |ui| self.show_queue(ui, &mut queue_actions));
+        }
+
+        if has_subscriptions || self.my_channel.is_some() {
+            egui::SidePanel::right("feed")
+                .default_width(260.0)
+                .show(ctx, |ui| self.show_feed(ui, &mut feed_actions));
+        }
+
+        // Connection status of the shared clients, refreshed as they connect or fail
+        let connections = self.pool.statuses();
+        if !connections.is_empty() {
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+
         // Multiple streams UI with scrollable list
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 ui.add_space(10.0);
 
+                if !connections.is_empty() {
+                    ui.horizontal(|ui| {
+                        for (network, status) in &connections {
+                            self.show_connection_status(ui, network, status);
+                        }
+                    });
+                }
+
                 // Address input with controls - always at top
                 ui.horizontal(|ui| {
                     ui.label("Address:");
                     let response = ui.add_sized(
                         [300.0, 22.0],
                         egui::TextEdit::singleline(&mut self.address_input)
-                            .hint_text("Enter video address..."),
+                            .hint_text("Enter video address or antube:// URI..."),
                     );
 
                     // Environment selector
+                    let networks = self.config.network_names();
                     egui::ComboBox::from_label("Env")
                         .selected_text(&self.selected_env)
                         .show_ui(ui, |ui| {
-                            for env in ENVIRONMENTS {
-                                ui.selectable_value(&mut self.selected_env, env.to_string(), env);
+                            for env in networks {
+                                ui.selectable_value(&mut self.selected_env, env.clone(), env);
                             }
                         });
 
@@ -255,6 +1033,52 @@ impl eframe::App for AntubeApp {
                         self.connect_and_stream();
                     }
 
+                    if ui.button("Queue").clicked() && !self.address_input.trim().is_empty() {
+                        if let Some(uri) = self.parse_address_input() {
+                            self.address_input.clear();
+                            let network = self.selected_env.clone();
+                            self.enqueue(
+                                vec![PlaylistItem {
+                                    address: uri.address,
+                                    network: uri.network,
+                                    title: None,
+                                }],
+                                network,
+                            );
+                        }
+                    }
+
+                    // Follow the address in the box as a channel
+                    if ui.button("Subscribe").clicked() && !self.address_input.trim().is_empty() {
+                        self.subscribe_to_channel();
+                    }
+
+                    ui.checkbox(&mut self.native_video, "Native window");
+
+                    let mut gateway_enabled = self.gateway.is_some();
+                    if ui
+                        .checkbox(&mut gateway_enabled, "HTTP gateway")
+                        .on_hover_text("Serve streams to external players like mpv or VLC")
+                        .changed()
+                    {
+                        self.toggle_gateway();
+                    }
+
+                    if self.history.is_some() && ui.button("History").clicked() {
+                        self.history_open = !self.history_open;
+                    }
+
+                    if ui.button("Settings").clicked() && self.settings_draft.is_none() {
+                        self.settings_draft = Some(self.file_config.clone());
+                    }
+
+                    // Upload button
+                    if ui.button("Upload").clicked() {
+                        self.pick_and_upload_file();
+                    }
+                    ui.checkbox(&mut self.upload_metadata, "With metadata");
+                    ui.checkbox(&mut self.upload_to_channel, "Publish to channel");
+
                     // Clear All button
                     if !self.streams.is_empty() && ui.button("Clear All").clicked() {
                         self.clear_all_streams();
@@ -266,9 +1090,35 @@ impl eframe::App for AntubeApp {
                     }
                 });
 
-                ui.add_space(15.0);
-
-                // Streams header
+                if let Some(error) = &self.address_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                if let Some(gateway) = &self.gateway {
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "Gateway serving http://{}/<network>/<address>",
+                            gateway.address()
+                        ))
+                        .size(11.0)
+                        .color(egui::Color32::GRAY),
+                    );
+                }
+                if let Some(error) = &self.gateway_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                }
+                match &self.subscribe_status {
+                    Some(Ok(status)) => {
+                        ui.label(egui::RichText::new(status).color(egui::Color32::GRAY));
+                    }
+                    Some(Err(error)) => {
+                        ui.colored_label(egui::Color32::RED, error);
+                    }
+                    None => {}
+                }
+
+                ui.add_space(15.0);
+
+                // Streams header
                 ui.horizontal(|ui| {
                     ui.label(
                         egui::RichText::new(format!("Streams ({})", self.streams.len()))
@@ -297,18 +1147,40 @@ impl eframe::App for AntubeApp {
                             streams.sort_by(|a, b| b.created_at.cmp(&a.created_at));
 
                             for stream in streams {
-                                self.show_stream_item(ui, stream);
+                                if let Some(action) = self.show_stream_item(ui, stream) {
+                                    stream_actions.push((stream.id, action));
+                                }
                                 ui.add_space(8.0);
                             }
                         }
                     });
             });
         });
+
+        for (stream_id, action) in stream_actions {
+            self.apply_stream_action(stream_id, action);
+        }
+        for action in feed_actions {
+            self.apply_feed_action(action);
+        }
+        for action in queue_actions {
+            self.apply_queue_action(action);
+        }
+        for action in history_actions {
+            self.apply_history_action(action);
+        }
+    }
+
+    /// Save the positions recorded since the last periodic save
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.save_history();
     }
 }
 
 impl AntubeApp {
-    fn show_stream_item(&self, ui: &mut egui::Ui, stream: &StreamInfo) {
+    fn show_stream_item(&self, ui: &mut egui::Ui, stream: &StreamInfo) -> Option<StreamAction> {
+        let mut action = None;
+
         egui::Frame::none()
             .fill(egui::Color32::from_gray(30))
             .rounding(4.0)
@@ -319,11 +1191,23 @@ impl AntubeApp {
                     self.show_stream_status_indicator(ui, &stream.status);
                     ui.add_space(8.0);
 
+                    // Thumbnail until the first video frame arrives
+                    if !self.video_textures.contains_key(&stream.id) {
+                        if let Some(thumbnail) = self.thumbnail_textures.get(&stream.id) {
+                            self.show_thumbnail(ui, thumbnail);
+                            ui.add_space(8.0);
+                        }
+                    }
+
                     // Stream info
                     ui.vertical(|ui| {
                         ui.horizontal(|ui| {
+                            let title = match &stream.metadata {
+                                Some(metadata) => &metadata.title,
+                                None => &stream.address,
+                            };
                             ui.label(
-                                egui::RichText::new(format!("#{} {}", stream.id, stream.address))
+                                egui::RichText::new(format!("#{} {title}", stream.id))
                                     .size(12.0)
                                     .strong(),
                             );
@@ -332,369 +1216,2611 @@ impl AntubeApp {
                                     .size(10.0)
                                     .color(egui::Color32::GRAY),
                             );
+                            if data_map::is_private(&stream.address) {
+                                ui.label(
+                                    egui::RichText::new("🔒 Private")
+                                        .size(10.0)
+                                        .color(egui::Color32::GOLD),
+                                )
+                                .on_hover_text("Played from a data map, kept out of logs, history and the cache");
+                            }
+                            if let Some(url) = self.player_url(stream) {
+                                if ui.small_button("Copy player URL").clicked() {
+                                    ui.output_mut(|o| o.copied_text = url);
+                                }
+                            }
                         });
 
+                        if let Some(metadata) = &stream.metadata {
+                            self.show_metadata(ui, &stream.address, metadata);
+                        }
+
                         // Status details
                         self.show_stream_status_details(ui, &stream.status);
+
+                        if let Some(stats) = &stream.cache_stats {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Cache: {} hits • {} misses",
+                                    stats.hits(),
+                                    stats.misses()
+                                ))
+                                .size(10.0)
+                                .color(egui::Color32::GRAY),
+                            );
+                        }
+
+                        if let Some(target) = &stream.prebuffer {
+                            ui.label(
+                                egui::RichText::new(self.format_prebuffer_target(target))
+                                    .size(10.0)
+                                    .color(egui::Color32::GRAY),
+                            );
+                        }
+
+                        if let Some(renditions) = self.format_renditions(&stream.renditions) {
+                            ui.label(
+                                egui::RichText::new(renditions)
+                                    .size(10.0)
+                                    .color(egui::Color32::LIGHT_BLUE),
+                            );
+                        }
+
+                        if let Some((attempt, max_retries)) = stream.retrying {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "Retrying ({attempt}/{max_retries})..."
+                                ))
+                                .size(11.0)
+                                .color(egui::Color32::YELLOW),
+                            );
+                        }
+
+                        if let Some(percent) = stream.buffering_percent {
+                            ui.label(
+                                egui::RichText::new(format!("Buffering {percent}%"))
+                                    .size(11.0)
+                                    .color(egui::Color32::YELLOW),
+                            );
+                        }
+
+                        // Inline video for embedded output
+                        if let Some(texture) = self.video_textures.get(&stream.id) {
+                            self.show_video_frame(ui, texture);
+                        }
+
+                        // Seek bar once the pipeline is running
+                        let video_streamer = self.video_streamers.get(&stream.id);
+                        if let Some(video_streamer) = video_streamer {
+                            action = self.show_seek_bar(ui, stream.id, video_streamer);
+                        }
+
+                        // Streams can be stopped from connecting on, paused once playing
+                        if video_streamer.is_some() || self.stream_tasks.contains_key(&stream.id) {
+                            if let Some(control) = self.show_playback_controls(
+                                ui,
+                                &stream.status,
+                                video_streamer.is_some(),
+                            ) {
+                                action = Some(control);
+                            }
+                        }
                     });
                 });
             });
+
+        action
     }
 
-    fn show_stream_status_indicator(&self, ui: &mut egui::Ui, status: &StreamStatus) {
-        match status {
-            StreamStatus::Connecting => {
-                ui.add(egui::Spinner::new().size(16.0));
-            }
-            StreamStatus::Streaming { .. } => {
-                ui.add(egui::Spinner::new().size(16.0));
-            }
-            StreamStatus::Completed { .. } => {
-                ui.label(
-                    egui::RichText::new("✅")
-                        .size(16.0)
-                        .color(egui::Color32::WHITE),
-                );
+    fn show_playback_controls(
+        &self,
+        ui: &mut egui::Ui,
+        status: &StreamStatus,
+        can_pause: bool,
+    ) -> Option<StreamAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            if can_pause {
+                if status.is_paused() {
+                    if ui.button("▶ Resume").clicked() {
+                        action = Some(StreamAction::Resume);
+                    }
+                } else if ui.button("⏸ Pause").clicked() {
+                    action = Some(StreamAction::Pause);
+                }
             }
-            StreamStatus::Error { .. } => {
-                ui.label("⚠️");
+
+            if ui.button("⏹ Stop").clicked() {
+                action = Some(StreamAction::Stop);
             }
-        }
+        });
+
+        action
     }
 
-    fn show_stream_status_details(&self, ui: &mut egui::Ui, status: &StreamStatus) {
-        match status {
-            StreamStatus::Connecting => {
-                ui.label(
-                    egui::RichText::new("Connecting to network...")
-                        .size(11.0)
-                        .color(egui::Color32::YELLOW),
-                );
-            }
-            StreamStatus::Streaming {
-                total_bytes_received,
-                chunks_received,
-                total_size,
-                ..
-            } => {
-                ui.horizontal(|ui| {
-                    ui.label(
-                        egui::RichText::new("Streaming:")
-                            .size(11.0)
-                            .color(egui::Color32::GREEN),
-                    );
+    /// Window editing the config, applied to new streams once saved
+    fn show_settings(&mut self, ctx: &egui::Context) {
+        let Some(mut draft) = self.settings_draft.take() else {
+            return;
+        };
 
-                    // Show progress with total size
-                    let progress_text = format!(
-                        "{}/{} streamed",
-                        self.format_data_size(*total_bytes_received),
-                        self.format_data_size(*total_size)
-                    );
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .default_width(380.0)
+            .show(ctx, |ui| {
+                if let Some(error) = &self.settings_error {
+                    ui.colored_label(egui::Color32::RED, error);
+                    ui.separator();
+                }
 
-                    ui.label(
-                        egui::RichText::new(progress_text)
-                            .size(11.0)
-                            .color(egui::Color32::WHITE),
-                    );
+                egui::Grid::new("settings")
+                    .num_columns(2)
+                    .spacing([12.0, 6.0])
+                    .show(ui, |ui| {
+                        ui.label("Network");
+                        let networks = draft.network_names();
+                        egui::ComboBox::from_id_source("settings_network")
+                            .selected_text(&draft.network)
+                            .show_ui(ui, |ui| {
+                                for env in networks {
+                                    ui.selectable_value(&mut draft.network, env.clone(), env);
+                                }
+                            });
+                        ui.end_row();
 
-                    // Show detailed chunk processing stats
-                    let chunk_stats_text = format!("• {chunks_received} chunks processed");
+                        ui.label("Connect at startup");
+                        ui.checkbox(&mut draft.connect_on_startup, "");
+                        ui.end_row();
 
-                    ui.label(
-                        egui::RichText::new(chunk_stats_text)
-                            .size(11.0)
-                            .color(egui::Color32::GRAY),
-                    );
-                });
-            }
-            StreamStatus::Completed {
-                total_bytes_received,
-                chunks_received,
-            } => {
+                        ui.label("Native window");
+                        ui.checkbox(&mut draft.native_video, "");
+                        ui.end_row();
+
+                        ui.label("Chunk cache");
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut draft.cache_enabled, "");
+                            ui.add(egui::DragValue::new(&mut draft.cache_size_mb).suffix(" MB"));
+                        });
+                        ui.end_row();
+
+                        ui.label("Retries");
+                        ui.add(egui::DragValue::new(&mut draft.max_retries).clamp_range(0..=100));
+                        ui.end_row();
+
+                        ui.label("Retry backoff");
+                        ui.add(egui::DragValue::new(&mut draft.retry_backoff_ms).suffix(" ms"));
+                        ui.end_row();
+
+                        ui.label("Prebuffer");
+                        ui.add(
+                            egui::DragValue::new(&mut draft.prebuffer_seconds)
+                                .speed(0.5)
+                                .suffix(" s"),
+                        );
+                        ui.end_row();
+
+                        ui.label("AppSrc buffer");
+                        ui.add(
+                            egui::DragValue::new(&mut draft.pipeline.appsrc_max_bytes)
+                                .speed(64 * 1024)
+                                .suffix(" bytes"),
+                        );
+                        ui.end_row();
+
+                        ui.label("Decodebin queue");
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut draft.pipeline.decodebin_max_size_bytes)
+                                    .speed(64 * 1024)
+                                    .suffix(" bytes"),
+                            );
+                            ui.add(
+                                egui::DragValue::new(&mut draft.pipeline.decodebin_max_size_ms)
+                                    .speed(100)
+                                    .clamp_range(1..=MAX_DECODEBIN_MAX_SIZE_MS)
+                                    .suffix(" ms"),
+                            );
+                        });
+                        ui.end_row();
+
+                        ui.label("Video sink");
+                        ui.text_edit_singleline(&mut draft.pipeline.video_sink);
+                        ui.end_row();
+
+                        ui.label("Audio sink");
+                        ui.text_edit_singleline(&mut draft.pipeline.audio_sink);
+                        ui.end_row();
+
+                        ui.label("Gateway port");
+                        ui.add(
+                            egui::DragValue::new(&mut draft.gateway_port).clamp_range(1..=65535),
+                        );
+                        ui.end_row();
+                    });
+
+                ui.separator();
                 ui.horizontal(|ui| {
-                    ui.label(
-                        egui::RichText::new("Completed:")
-                            .size(11.0)
-                            .color(egui::Color32::GREEN),
-                    );
-                    ui.label(
-                        egui::RichText::new(format!(
-                            "{} • {} chunks",
-                            self.format_data_size(*total_bytes_received),
-                            chunks_received
-                        ))
-                        .size(11.0)
-                        .color(egui::Color32::WHITE),
-                    );
+                    save = ui.button("Save").clicked();
+                    if ui.button("Defaults").clicked() {
+                        draft = Config::default();
+                    }
                 });
-            }
-            StreamStatus::Error { message } => {
                 ui.label(
-                    egui::RichText::new(format!("Error: {}", message))
-                        .size(11.0)
-                        .color(egui::Color32::RED),
+                    egui::RichText::new("Applies to new streams, cache changes after a restart")
+                        .size(10.0)
+                        .color(egui::Color32::GRAY),
                 );
+            });
+
+        if save {
+            match self.save_settings(&draft) {
+                Ok(()) => {
+                    println!("Settings saved");
+                    self.native_video = draft.native_video;
+                    self.selected_env = draft.network.clone();
+                    self.pool.set_networks(draft.networks.clone());
+                    // Saved settings take over from the command line for new streams
+                    self.config = draft.clone();
+                    self.file_config = draft;
+                    self.settings_error = None;
+                    return;
+                }
+                Err(e) => self.settings_error = Some(e),
             }
         }
+        if open {
+            self.settings_draft = Some(draft);
+        }
     }
 
-    fn connect_and_stream(&mut self) {
-        // Create new stream with unique ID
-        let stream_id = self.next_stream_id;
-        self.next_stream_id += 1;
+    fn save_settings(&self, config: &Config) -> Result<(), String> {
+        config.validate()?;
+        match &self.config_path {
+            Some(path) => config.save(path),
+            None => Err("No config directory to save the settings to".to_string()),
+        }
+    }
 
-        let address = self.address_input.clone();
-        let environment = self.selected_env.clone();
+    /// Start the HTTP gateway on the configured port, or stop it if running
+    fn toggle_gateway(&mut self) {
+        if self.gateway.take().is_some() {
+            println!("Gateway stopped");
+            return;
+        }
+        let bind = SocketAddr::from((Ipv4Addr::LOCALHOST, self.config.gateway_port));
+        match Gateway::start(bind, self.pool.source_provider()) {
+            Ok(gateway) => {
+                self.gateway = Some(gateway);
+                self.gateway_error = None;
+            }
+            Err(e) => self.gateway_error = Some(e),
+        }
+    }
 
-        // Create stream info
-        let stream_info = StreamInfo {
-            id: stream_id,
-            address: address.clone(),
-            environment: environment.clone(),
-            status: StreamStatus::Connecting,
-            created_at: std::time::Instant::now(),
+    /// Gateway URL of the data a stream plays, while the gateway is running
+    fn player_url(&self, stream: &StreamInfo) -> Option<String> {
+        let gateway = self.gateway.as_ref()?;
+        if matches!(
+            stream.status,
+            StreamStatus::Uploading { .. } | StreamStatus::Uploaded { .. }
+        ) {
+            return None;
+        }
+        // Metadata addresses aren't playable, the content they point at is
+        let address = match &stream.metadata {
+            Some(metadata) => &metadata.content,
+            None => &stream.address,
         };
+        Some(gateway.url(&stream.environment, address))
+    }
 
-        // Add to streams map
-        self.streams.insert(stream_id, stream_info);
-
-        // Get the shared sender channel
-        let stream_tx = self.stream_sender.clone();
+    fn show_connection_status(&self, ui: &mut egui::Ui, network: &str, status: &ConnectionStatus) {
+        let (color, label) = match status {
+            ConnectionStatus::Connecting => (egui::Color32::YELLOW, "connecting"),
+            ConnectionStatus::Connected => (egui::Color32::GREEN, "connected"),
+            ConnectionStatus::Failed(_) => (egui::Color32::RED, "disconnected"),
+        };
+        let response = ui.label(
+            egui::RichText::new(format!("● {network}: {label}"))
+                .size(11.0)
+                .color(color),
+        );
+        if let ConnectionStatus::Failed(error) = status {
+            response.on_hover_text(error);
+        }
+    }
 
-        let (server_tx, server_rx) = mpsc::unbounded_channel();
+    /// Watched videos, most recent first, with their progress
+    fn show_history(&self, ui: &mut egui::Ui, actions: &mut Vec<HistoryAction>) {
+        let Some(history) = &self.history else {
+            return;
+        };
 
-        // Spawn server initialization task
-        tokio::spawn(async move {
-            let result = Server::new(&environment).await;
-            let _ = server_tx.send(result);
-        });
+        if history.entries().is_empty() {
+            ui.label(
+                egui::RichText::new("Nothing watched yet")
+                    .size(12.0)
+                    .color(egui::Color32::GRAY),
+            );
+            return;
+        }
+        if ui.small_button("Clear history").clicked() {
+            actions.push(HistoryAction::Clear);
+        }
+        ui.separator();
 
-        // Start new streaming task and store handle
-        let task = tokio::spawn(Self::run_streaming_task(
-            stream_id, server_rx, stream_tx, address,
-        ));
-        self.stream_tasks.insert(stream_id, task);
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for entry in history.entries() {
+                ui.horizontal(|ui| {
+                    if entry.resume_position().is_some() && ui.small_button("Resume").clicked() {
+                        actions.push(HistoryAction::Resume(entry.clone()));
+                    }
+                    if ui.small_button("▶").clicked() {
+                        actions.push(HistoryAction::Play(entry.clone()));
+                    }
+                    if ui.small_button("✖").clicked() {
+                        actions.push(HistoryAction::Remove(entry.clone()));
+                    }
 
-        println!(
-            "Started stream {} for address {}",
-            stream_id, self.address_input
-        );
+                    let title = entry
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| self.format_short_address(&entry.address));
+                    ui.label(egui::RichText::new(title).size(11.0).strong());
 
-        // Clear input for next stream
-        self.address_input.clear();
+                    let progress = if entry.completed {
+                        "watched".to_string()
+                    } else {
+                        match entry.duration {
+                            Some(duration) => format!(
+                                "{} / {}",
+                                self.format_duration(entry.position),
+                                self.format_duration(duration)
+                            ),
+                            None => self.format_duration(entry.position),
+                        }
+                    };
+                    ui.label(
+                        egui::RichText::new(format!("{progress} ({})", entry.environment))
+                            .size(10.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                });
+            }
+        });
     }
 
-    async fn run_streaming_task(
-        stream_id: StreamId,
-        server_rx: mpsc::UnboundedReceiver<Result<Server, String>>,
-        stream_tx: mpsc::UnboundedSender<StreamEvent>,
-        address: String,
-    ) {
-        let server = match Self::wait_for_server(stream_id, server_rx, &stream_tx).await {
-            Some(server) => server,
-            None => return,
-        };
-
-        Self::stream_video_data(stream_id, server, address, stream_tx).await;
-    }
+    /// Queued items in play order with shuffle, repeat and playlist controls
+    fn show_queue(&self, ui: &mut egui::Ui, actions: &mut Vec<QueueAction>) {
+        ui.add_space(10.0);
+        ui.label(
+            egui::RichText::new(format!("Queue ({})", self.queue.items().len()))
+                .size(16.0)
+                .strong(),
+        );
 
-    async fn wait_for_server(
-        stream_id: StreamId,
-        mut server_rx: mpsc::UnboundedReceiver<Result<Server, String>>,
-        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
-    ) -> Option<Server> {
-        match server_rx.recv().await {
-            Some(Ok(server)) => {
-                // We'll send ServerConnected with total_size from stream_video_data after getting DataStream
-                Some(server)
+        ui.horizontal(|ui| {
+            if ui.small_button("⏭ Next").clicked() {
+                actions.push(QueueAction::Next);
             }
-            Some(Err(error)) => {
-                let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
-                None
+            if ui
+                .selectable_label(self.queue.is_shuffled(), "Shuffle")
+                .clicked()
+            {
+                actions.push(QueueAction::ToggleShuffle);
             }
-            None => {
-                let _ = stream_tx.send(StreamEvent::StreamError {
-                    stream_id,
-                    error: "Server initialization failed".to_string(),
-                });
-                None
+            if ui.small_button(self.queue.repeat.label()).clicked() {
+                actions.push(QueueAction::CycleRepeat);
             }
+        });
+        ui.horizontal(|ui| {
+            if ui.small_button("Open...").clicked() {
+                actions.push(QueueAction::Open);
+            }
+            if ui.small_button("Save...").clicked() {
+                actions.push(QueueAction::Save);
+            }
+            if ui.small_button("Publish").clicked() {
+                actions.push(QueueAction::Publish);
+            }
+            if ui.small_button("Clear").clicked() {
+                actions.push(QueueAction::Clear);
+            }
+        });
+
+        if let Some(status) = &self.queue_status {
+            ui.label(
+                egui::RichText::new(status)
+                    .size(10.0)
+                    .color(egui::Color32::GRAY),
+            );
+        }
+        ui.separator();
+
+        let current = self.queue.current().filter(|_| self.queue_stream.is_some());
+        let next = self.queue.peek_next();
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for &index in self.queue.order() {
+                let item = &self.queue.items()[index];
+                ui.horizontal(|ui| {
+                    let is_current = current == Some(index);
+                    if is_current {
+                        ui.label(egui::RichText::new("▶").color(egui::Color32::GREEN));
+                    } else {
+                        if ui.small_button("▶").clicked() {
+                            actions.push(QueueAction::Play(index));
+                        }
+                        if ui.small_button("✖").clicked() {
+                            actions.push(QueueAction::Remove(index));
+                        }
+                    }
+
+                    let title = item
+                        .title
+                        .clone()
+                        .unwrap_or_else(|| self.format_short_address(&item.address));
+                    let mut text = egui::RichText::new(title).size(11.0);
+                    if is_current {
+                        text = text.strong();
+                    }
+                    ui.label(text);
+
+                    if next == Some(index) && !is_current && self.queue_stream.is_some() {
+                        ui.label(
+                            egui::RichText::new("up next")
+                                .size(10.0)
+                                .color(egui::Color32::GRAY),
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    /// Videos of the subscribed channels, unseen ones highlighted
+    fn show_feed(&self, ui: &mut egui::Ui, actions: &mut Vec<FeedAction>) {
+        ui.add_space(10.0);
+        ui.label(egui::RichText::new("Feed").size(16.0).strong());
+
+        if let Some(channel) = &self.my_channel {
+            ui.label(
+                egui::RichText::new(format!("Your channel: {channel}"))
+                    .size(10.0)
+                    .color(egui::Color32::GRAY),
+            );
+        }
+
+        let Some(subscriptions) = &self.subscriptions else {
+            return;
+        };
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for channel in subscriptions.channels() {
+                ui.separator();
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new(self.format_short_address(&channel.address))
+                            .size(12.0)
+                            .strong(),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!("({})", channel.environment))
+                            .size(10.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                    if ui.small_button("Unsubscribe").clicked() {
+                        actions.push(FeedAction::Unsubscribe {
+                            channel: channel.address.clone(),
+                        });
+                    }
+                });
+
+                let videos = match self.feed.get(&channel.address) {
+                    Some(Ok(videos)) => videos,
+                    Some(Err(error)) => {
+                        ui.label(
+                            egui::RichText::new(error)
+                                .size(10.0)
+                                .color(egui::Color32::RED),
+                        );
+                        continue;
+                    }
+                    None => {
+                        ui.label(
+                            egui::RichText::new("Loading...")
+                                .size(10.0)
+                                .color(egui::Color32::GRAY),
+                        );
+                        continue;
+                    }
+                };
+
+                let new_count = videos
+                    .iter()
+                    .filter(|video| subscriptions.is_new(&channel.address, &video.address))
+                    .count();
+                if new_count > 0 {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(format!("{new_count} new"))
+                                .size(11.0)
+                                .color(egui::Color32::GREEN),
+                        );
+                        if ui.small_button("Mark seen").clicked() {
+                            actions.push(FeedAction::MarkSeen {
+                                channel: channel.address.clone(),
+                            });
+                        }
+                    });
+                } else if videos.is_empty() {
+                    ui.label(
+                        egui::RichText::new("No videos yet")
+                            .size(10.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                }
+
+                for video in videos {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("▶").clicked() {
+                            actions.push(FeedAction::Play {
+                                channel: channel.address.clone(),
+                                video: video.address.clone(),
+                                environment: channel.environment.clone(),
+                            });
+                        }
+
+                        let title = video
+                            .title
+                            .clone()
+                            .unwrap_or_else(|| self.format_short_address(&video.address));
+                        let mut text = egui::RichText::new(title).size(11.0);
+                        if subscriptions.is_new(&channel.address, &video.address) {
+                            text = text.strong().color(egui::Color32::GREEN);
+                        }
+                        ui.label(text);
+                    });
+                }
+            }
+        });
+    }
+
+    fn show_metadata(&self, ui: &mut egui::Ui, address: &str, metadata: &VideoMetadata) {
+        if !metadata.description.is_empty() {
+            ui.label(egui::RichText::new(&metadata.description).size(11.0));
+        }
+
+        let mut details = vec![address.to_string()];
+        if let Some(duration) = metadata.duration {
+            details.push(self.format_duration(duration));
+        }
+        if let Some(mime_type) = &metadata.mime_type {
+            details.push(mime_type.clone());
+        }
+        ui.label(
+            egui::RichText::new(details.join(" • "))
+                .size(10.0)
+                .color(egui::Color32::GRAY),
+        );
+    }
+
+    fn show_thumbnail(&self, ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+        const THUMBNAIL_WIDTH: f32 = 120.0;
+
+        let [width, height] = texture.size();
+        let scale = THUMBNAIL_WIDTH / width.max(1) as f32;
+        let size = egui::vec2(THUMBNAIL_WIDTH, height as f32 * scale);
+        ui.image((texture.id(), size));
+    }
+
+    fn show_video_frame(&self, ui: &mut egui::Ui, texture: &egui::TextureHandle) {
+        const MAX_VIDEO_WIDTH: f32 = 480.0;
+
+        let [width, height] = texture.size();
+        let width_limit = ui.available_width().min(MAX_VIDEO_WIDTH);
+        let scale = (width_limit / width as f32).min(1.0);
+        let size = egui::vec2(width as f32 * scale, height as f32 * scale);
+
+        ui.image((texture.id(), size));
+    }
+
+    fn show_seek_bar(
+        &self,
+        ui: &mut egui::Ui,
+        stream_id: StreamId,
+        streamer: &VideoStreamer,
+    ) -> Option<StreamAction> {
+        let duration = streamer.duration_seconds()?;
+        let mut action = None;
+
+        // While dragging, show the dragged position instead of the playback position
+        let drag_id = egui::Id::new(("seek", stream_id));
+        let mut position = ui
+            .data(|d| d.get_temp::<f64>(drag_id))
+            .or_else(|| streamer.position_seconds())
+            .unwrap_or(0.0);
+
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(self.format_duration(position))
+                    .size(11.0)
+                    .color(egui::Color32::WHITE),
+            );
+
+            ui.spacing_mut().slider_width = 300.0;
+            let response =
+                ui.add(egui::Slider::new(&mut position, 0.0..=duration).show_value(false));
+
+            ui.label(
+                egui::RichText::new(self.format_duration(duration))
+                    .size(11.0)
+                    .color(egui::Color32::GRAY),
+            );
+
+            if response.dragged() {
+                ui.data_mut(|d| d.insert_temp(drag_id, position));
+            } else if response.drag_released() || response.changed() {
+                ui.data_mut(|d| d.remove::<f64>(drag_id));
+                action = Some(StreamAction::Seek(position));
+            }
+        });
+
+        action
+    }
+
+    fn show_stream_status_indicator(&self, ui: &mut egui::Ui, status: &StreamStatus) {
+        match status {
+            StreamStatus::Connecting => {
+                ui.add(egui::Spinner::new().size(16.0));
+            }
+            StreamStatus::Streaming { .. } => {
+                ui.add(egui::Spinner::new().size(16.0));
+            }
+            StreamStatus::Completed { paused: true, .. } => {
+                ui.label(
+                    egui::RichText::new("⏸")
+                        .size(16.0)
+                        .color(egui::Color32::WHITE),
+                );
+            }
+            StreamStatus::Completed { .. } => {
+                ui.label(
+                    egui::RichText::new("✅")
+                        .size(16.0)
+                        .color(egui::Color32::WHITE),
+                );
+            }
+            StreamStatus::PlaybackFinished { .. } => {
+                ui.label(
+                    egui::RichText::new("🏁")
+                        .size(16.0)
+                        .color(egui::Color32::WHITE),
+                );
+            }
+            StreamStatus::Stopped => {
+                ui.label(
+                    egui::RichText::new("⏹")
+                        .size(16.0)
+                        .color(egui::Color32::GRAY),
+                );
+            }
+            StreamStatus::DecodeFailed { .. } => {
+                ui.label("⚠️");
+            }
+            StreamStatus::Error { .. } => {
+                ui.label("⚠️");
+            }
+            StreamStatus::Uploading { .. } => {
+                ui.add(egui::Spinner::new().size(16.0));
+            }
+            StreamStatus::Uploaded { .. } => {
+                ui.label(
+                    egui::RichText::new("⬆")
+                        .size(16.0)
+                        .color(egui::Color32::WHITE),
+                );
+            }
+        }
+    }
+
+    fn show_stream_status_details(&self, ui: &mut egui::Ui, status: &StreamStatus) {
+        match status {
+            StreamStatus::Connecting => {
+                ui.label(
+                    egui::RichText::new("Connecting to network...")
+                        .size(11.0)
+                        .color(egui::Color32::YELLOW),
+                );
+            }
+            StreamStatus::Streaming {
+                total_bytes_received,
+                chunks_received,
+                total_size,
+                paused,
+                stats,
+            } => {
+                ui.horizontal(|ui| {
+                    let label = if *paused { "Paused:" } else { "Streaming:" };
+                    ui.label(
+                        egui::RichText::new(label)
+                            .size(11.0)
+                            .color(egui::Color32::GREEN),
+                    );
+
+                    // Show progress with total size
+                    let progress_text = format!(
+                        "{}/{} streamed",
+                        self.format_data_size(*total_bytes_received),
+                        self.format_data_size(*total_size)
+                    );
+
+                    ui.label(
+                        egui::RichText::new(progress_text)
+                            .size(11.0)
+                            .color(egui::Color32::WHITE),
+                    );
+
+                    // Show detailed chunk processing stats
+                    let chunk_stats_text = format!("• {chunks_received} chunks processed");
+
+                    ui.label(
+                        egui::RichText::new(chunk_stats_text)
+                            .size(11.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                });
+
+                self.show_transfer_stats(
+                    ui,
+                    stats,
+                    total_size.saturating_sub(*total_bytes_received),
+                );
+            }
+            StreamStatus::Completed {
+                total_bytes_received,
+                chunks_received,
+                paused,
+                stats,
+            } => {
+                ui.horizontal(|ui| {
+                    let label = if *paused {
+                        "Downloaded (paused):"
+                    } else {
+                        "Downloaded:"
+                    };
+                    ui.label(
+                        egui::RichText::new(label)
+                            .size(11.0)
+                            .color(egui::Color32::GREEN),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} • {} chunks",
+                            self.format_data_size(*total_bytes_received),
+                            chunks_received
+                        ))
+                        .size(11.0)
+                        .color(egui::Color32::WHITE),
+                    );
+                    ui.label(
+                        egui::RichText::new(self.format_playback_stats(stats))
+                            .size(11.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                });
+            }
+            StreamStatus::PlaybackFinished {
+                total_bytes_received,
+                chunks_received,
+                stats,
+            } => {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new("Playback complete:")
+                            .size(11.0)
+                            .color(egui::Color32::GREEN),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} • {} chunks",
+                            self.format_data_size(*total_bytes_received),
+                            chunks_received
+                        ))
+                        .size(11.0)
+                        .color(egui::Color32::WHITE),
+                    );
+                    ui.label(
+                        egui::RichText::new(self.format_playback_stats(stats))
+                            .size(11.0)
+                            .color(egui::Color32::GRAY),
+                    );
+                });
+            }
+            StreamStatus::DecodeFailed { message } => {
+                ui.label(
+                    egui::RichText::new(format!("Playback failed: {}", message))
+                        .size(11.0)
+                        .color(egui::Color32::RED),
+                );
+            }
+            StreamStatus::Stopped => {
+                ui.label(
+                    egui::RichText::new("Stopped")
+                        .size(11.0)
+                        .color(egui::Color32::GRAY),
+                );
+            }
+            StreamStatus::Error { message } => {
+                ui.label(
+                    egui::RichText::new(format!("Error: {}", message))
+                        .size(11.0)
+                        .color(egui::Color32::RED),
+                );
+            }
+            StreamStatus::Uploading { stage, total_size } => {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new("Uploading:")
+                            .size(11.0)
+                            .color(egui::Color32::YELLOW),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!(
+                            "{} • {}",
+                            stage,
+                            self.format_data_size(*total_size)
+                        ))
+                        .size(11.0)
+                        .color(egui::Color32::WHITE),
+                    );
+                });
+            }
+            StreamStatus::Uploaded { address, cost } => {
+                ui.horizontal(|ui| {
+                    ui.label(
+                        egui::RichText::new("Uploaded:")
+                            .size(11.0)
+                            .color(egui::Color32::GREEN),
+                    );
+                    ui.label(
+                        egui::RichText::new(format!("{address} • cost {cost}"))
+                            .size(11.0)
+                            .color(egui::Color32::WHITE),
+                    );
+                    if ui.small_button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = address.clone());
+                    }
+                });
+            }
+        }
+    }
+
+    /// Download speed, ETA, time to first frame and stalls of a stream still downloading
+    fn show_transfer_stats(&self, ui: &mut egui::Ui, stats: &TransferStats, remaining: usize) {
+        let now = std::time::Instant::now();
+
+        ui.horizontal(|ui| {
+            self.show_speed_sparkline(ui, &stats.speed_history(now));
+
+            let mut text = format!(
+                "{}/s",
+                self.format_data_size(stats.throughput(now) as usize)
+            );
+            if let Some(eta) = stats.eta(remaining, now) {
+                text += &format!(" • ETA {}", self.format_duration(eta.as_secs_f64()));
+            }
+            text += &format!(" • {}", self.format_playback_stats(stats));
+
+            ui.label(
+                egui::RichText::new(text)
+                    .size(10.0)
+                    .color(egui::Color32::GRAY),
+            );
+
+            if let Some(stall) = stats.current_stall(now) {
+                ui.label(
+                    egui::RichText::new(format!("Stalled {:.0}s", stall.as_secs_f64()))
+                        .size(10.0)
+                        .color(egui::Color32::YELLOW),
+                );
+            }
+        });
+    }
+
+    fn show_speed_sparkline(&self, ui: &mut egui::Ui, history: &[f64]) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(90.0, 16.0), egui::Sense::hover());
+        let max = history.iter().cloned().fold(0.0, f64::max);
+        if max <= 0.0 || history.len() < 2 {
+            return;
+        }
+
+        let step = rect.width() / (history.len() - 1) as f32;
+        let points = history
+            .iter()
+            .enumerate()
+            .map(|(i, speed)| {
+                let height = (speed / max) as f32 * rect.height();
+                egui::pos2(rect.left() + i as f32 * step, rect.bottom() - height)
+            })
+            .collect();
+        ui.painter().add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+        ));
+    }
+
+    fn format_prebuffer_target(&self, target: &PrebufferTarget) -> String {
+        let size = self.format_data_size(target.bytes);
+        match (target.seconds, target.bitrate) {
+            (Some(seconds), Some(bitrate)) => format!(
+                "Prebuffer: {size} ({seconds:.0}s at {:.1} Mbit/s)",
+                bitrate * 8.0 / 1_000_000.0
+            ),
+            _ => format!("Prebuffer: {size} (bitrate unknown)"),
+        }
+    }
+
+    /// Active rendition followed by the switch history, `None` unless renditions were switched between
+    fn format_renditions(&self, renditions: &[(String, usize)]) -> Option<String> {
+        let (active, _) = renditions.last()?;
+        let history = renditions
+            .windows(2)
+            .map(|pair| format!("{} → {} @seg {}", pair[0].0, pair[1].0, pair[1].1))
+            .collect::<Vec<_>>();
+        Some(if history.is_empty() {
+            format!("Rendition: {active}")
+        } else {
+            format!("Rendition: {active} • {}", history.join(", "))
+        })
+    }
+
+    fn format_playback_stats(&self, stats: &TransferStats) -> String {
+        let first_frame = match stats.time_to_first_frame() {
+            Some(after) => format!("first frame {:.1}s", after.as_secs_f64()),
+            None => "no frame yet".to_string(),
+        };
+        let (stall_count, stall_time) = stats.stalls();
+        format!(
+            "{first_frame} • {stall_count} stalls ({:.0}s)",
+            stall_time.as_secs_f64()
+        )
+    }
+
+    /// Upload the latest decoded frame of each embedded stream into its texture
+    fn update_video_textures(&mut self, ctx: &egui::Context) {
+        let mut has_embedded_video = false;
+
+        for (stream_id, streamer) in &self.video_streamers {
+            if streamer.video_output() != VideoOutput::Embedded {
+                continue;
+            }
+            has_embedded_video = true;
+
+            let Some(frame) = streamer.take_frame() else {
+                continue;
+            };
+            let image = egui::ColorImage::from_rgba_unmultiplied(
+                [frame.width, frame.height],
+                &frame.pixels,
+            );
+
+            match self.video_textures.get_mut(stream_id) {
+                Some(texture) => texture.set(image, egui::TextureOptions::LINEAR),
+                None => {
+                    let texture = ctx.load_texture(
+                        format!("video-{stream_id}"),
+                        image,
+                        egui::TextureOptions::LINEAR,
+                    );
+                    self.video_textures.insert(*stream_id, texture);
+                }
+            }
+        }
+
+        // Poll for new frames at video frame rate
+        if has_embedded_video {
+            ctx.request_repaint_after(std::time::Duration::from_millis(16));
+        }
+    }
+
+    /// What the address box names to play, `None` with the error shown when it can't be played
+    fn parse_address_input(&mut self) -> Option<AntubeUri> {
+        // antube:// URIs pick their own network and start position
+        let mut uri = match AntubeUri::parse(&self.address_input) {
+            Ok(uri) => uri,
+            Err(e) => {
+                self.address_error = Some(e);
+                return None;
+            }
+        };
+        // Private data maps, inline or in a file, play under a stand-in address
+        match data_map::resolve_input(&uri.address) {
+            Ok(Some(private)) => uri.address = private,
+            Ok(None) => {}
+            Err(e) => {
+                self.address_error = Some(e);
+                return None;
+            }
+        }
+        self.address_error = None;
+        Some(uri)
+    }
+
+    fn connect_and_stream(&mut self) {
+        let Some(uri) = self.parse_address_input() else {
+            return;
+        };
+
+        let environment = uri.network.unwrap_or_else(|| self.selected_env.clone());
+        let stream_id = self.start_stream(uri.address, environment, false, 0);
+        if let Some(start) = uri.start.filter(|start| *start > 0.0) {
+            self.pending_seeks.insert(stream_id, start);
+        }
+
+        // Clear input for next stream
+        self.address_input.clear();
+    }
+
+    /// Seek streams opened at a URI's start position once their pipeline can seek
+    fn apply_pending_seeks(&mut self) {
+        let mut done = Vec::new();
+        for (stream_id, position) in &self.pending_seeks {
+            let Some(streamer) = self.video_streamers.get(stream_id) else {
+                continue;
+            };
+            if streamer.duration_seconds().is_none() {
+                continue;
+            }
+            match streamer.seek_to(*position) {
+                Ok(()) => println!("Stream {stream_id} started at {position:.0}s"),
+                Err(e) => println!("Stream {stream_id} can't seek to {position:.0}s: {e}"),
+            }
+            done.push(*stream_id);
+        }
+        for stream_id in done {
+            self.pending_seeks.remove(&stream_id);
+        }
+    }
+
+    /// Open URIs handed over by later launches of the app, as new streams
+    fn listen_for_uris(&self, ctx: &egui::Context) {
+        let stream_tx = self.stream_sender.clone();
+        let ctx = ctx.clone();
+        let result = instance::listen(move |uri| {
+            let _ = stream_tx.send(StreamEvent::OpenUri { uri });
+            ctx.request_repaint();
+        });
+        if let Err(e) = result {
+            println!("Single instance handoff disabled: {e}");
+        }
+    }
+
+    fn start_stream(
+        &mut self,
+        address: String,
+        environment: String,
+        start_paused: bool,
+        start_offset: usize,
+    ) -> StreamId {
+        // Create new stream with unique ID
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 1;
+
+        let options = StreamOptions {
+            video_output: if self.native_video {
+                VideoOutput::Native
+            } else {
+                VideoOutput::Embedded
+            },
+            retry: self.config.retry_policy(),
+            prebuffer_seconds: self.config.prebuffer_seconds,
+            start_paused,
+            start_offset,
+            pipeline: self.config.pipeline.clone(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+        self.stream_cancels
+            .insert(stream_id, options.cancelled.clone());
+
+        // Private data isn't written to the on-disk cache
+        let chunk_cache = self
+            .chunk_cache
+            .clone()
+            .filter(|_| !data_map::is_private(&address));
+        let cache_stats = Arc::new(CacheStats::default());
+
+        // Create stream info
+        let stream_info = StreamInfo {
+            id: stream_id,
+            address: address.clone(),
+            environment: environment.clone(),
+            status: StreamStatus::Connecting,
+            created_at: std::time::Instant::now(),
+            buffering_percent: None,
+            cache_stats: chunk_cache.is_some().then(|| cache_stats.clone()),
+            retrying: None,
+            prebuffer: None,
+            renditions: Vec::new(),
+            metadata: None,
+            resumed_from: None,
+        };
+
+        // Add to streams map
+        self.streams.insert(stream_id, stream_info);
+
+        // Get the shared sender channel
+        let stream_tx = self.stream_sender.clone();
+
+        let (server_tx, server_rx) = mpsc::unbounded_channel();
+        let pool = self.pool.clone();
+
+        // Spawn server initialization task
+        tokio::spawn(async move {
+            let result = pool.get(&environment).await.map(|server| {
+                let server = Arc::new(server) as Arc<dyn DataSource>;
+                match chunk_cache {
+                    Some(cache) => Arc::new(CachedSource::new(server, cache, cache_stats))
+                        as Arc<dyn DataSource>,
+                    None => server,
+                }
+            });
+            let _ = server_tx.send(result);
+        });
+
+        // Start new streaming task and store handle
+        let task = tokio::spawn(Self::run_streaming_task(
+            stream_id,
+            server_rx,
+            stream_tx,
+            address.clone(),
+            options,
+        ));
+        self.stream_tasks.insert(stream_id, task);
+
+        println!("Started stream {stream_id} for address {address}");
+        stream_id
+    }
+
+    async fn run_streaming_task(
+        stream_id: StreamId,
+        server_rx: mpsc::UnboundedReceiver<Result<Arc<dyn DataSource>, String>>,
+        stream_tx: mpsc::UnboundedSender<StreamEvent>,
+        address: String,
+        options: StreamOptions,
+    ) {
+        let source = match Self::wait_for_server(stream_id, server_rx, &stream_tx).await {
+            Some(source) => source,
+            None => return,
+        };
+
+        let result =
+            Self::resolve_document(stream_id, source.as_ref(), address, &options, &stream_tx).await;
+        let (address, sniffed) = match result {
+            Ok(Some(resolved)) => resolved,
+            Ok(None) => return,
+            Err(error) => {
+                let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
+                return;
+            }
+        };
+
+        // Manifest addresses play their segments back to back, rendition switches of adaptive
+        // manifests show up on the stream card
+        let source = PrefetchedSource::new(source, &address, sniffed);
+        let rendition_tx = stream_tx.clone();
+        let source = ManifestSource::new(Arc::new(source)).with_rendition_handler(move |change| {
+            let _ = rendition_tx.send(StreamEvent::RenditionChanged { stream_id, change });
+        });
+
+        Self::stream_video_data(stream_id, Arc::new(source), address, options, stream_tx).await;
+    }
+
+    /// Sniff the address once to tell playlists, metadata and manifests apart. Playlists fill the
+    /// queue instead of playing (`None`), metadata is reported and followed to its content.
+    /// Returns the address to play with what was sniffed of it.
+    async fn resolve_document(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: String,
+        options: &StreamOptions,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<Option<(String, Sniffed)>, String> {
+        let sniffed =
+            Self::sniff_with_retry(stream_id, source, &address, &options.retry, stream_tx).await?;
+        let data = match &sniffed {
+            Sniffed::Small(data) => data,
+            Sniffed::Large(_) => return Ok(Some((address, sniffed))),
+        };
+
+        if let Some(playlist) = Playlist::parse(data) {
+            let _ = stream_tx.send(StreamEvent::PlaylistLoaded {
+                stream_id,
+                playlist,
+            });
+            return Ok(None);
+        }
+
+        let Some(metadata) = VideoMetadata::parse(data) else {
+            return Ok(Some((address, sniffed)));
+        };
+        let content = Self::report_metadata(stream_id, source, &address, metadata, stream_tx).await;
+        let sniffed =
+            Self::sniff_with_retry(stream_id, source, &content, &options.retry, stream_tx).await?;
+        Ok(Some((content, sniffed)))
+    }
+
+    async fn sniff_with_retry(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        retry: &RetryPolicy,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<Sniffed, String> {
+        let mut attempt = 0;
+        loop {
+            match sniff(source, address, MAX_DOCUMENT_SIZE).await {
+                Ok(sniffed) => return Ok(sniffed),
+                Err(error) => {
+                    attempt += 1;
+                    Self::wait_before_retry(stream_id, attempt, error, retry, stream_tx).await?;
+                }
+            }
+        }
+    }
+
+    async fn open_ranged_with_retry(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        retry: &RetryPolicy,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<BlockingRangeReader, String> {
+        let mut attempt = 0;
+        loop {
+            match source.open_ranged(address).await {
+                Ok(reader) => return Ok(BlockingRangeReader::new(reader)),
+                Err(error) => {
+                    attempt += 1;
+                    Self::wait_before_retry(stream_id, attempt, error, retry, stream_tx).await?;
+                }
+            }
+        }
+    }
+
+    /// Show the title and thumbnail of a metadata address, returns the content it describes
+    async fn report_metadata(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        metadata: VideoMetadata,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> String {
+        println!(
+            "Address {address} is metadata for {:?} at {}",
+            metadata.title, metadata.content
+        );
+
+        // A missing thumbnail doesn't stop playback
+        let thumbnail = match &metadata.thumbnail {
+            Some(thumbnail) => match Self::load_thumbnail(source, thumbnail).await {
+                Ok(image) => Some(image),
+                Err(e) => {
+                    println!("Failed to load thumbnail {thumbnail}: {e}");
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let content = metadata.content.clone();
+        let _ = stream_tx.send(StreamEvent::MetadataLoaded {
+            stream_id,
+            metadata,
+            thumbnail,
+        });
+        content
+    }
+
+    async fn load_thumbnail(
+        source: &dyn DataSource,
+        address: &str,
+    ) -> Result<egui::ColorImage, String> {
+        let data = metadata::fetch_thumbnail(source, address).await?;
+        let decoded = image::load_from_memory(&data)
+            .map_err(|e| format!("Invalid image: {e}"))?
+            .into_rgba8();
+        let (width, height) = decoded.dimensions();
+        Ok(egui::ColorImage::from_rgba_unmultiplied(
+            [width as usize, height as usize],
+            decoded.as_raw(),
+        ))
+    }
+
+    async fn wait_for_server(
+        stream_id: StreamId,
+        mut server_rx: mpsc::UnboundedReceiver<Result<Arc<dyn DataSource>, String>>,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Option<Arc<dyn DataSource>> {
+        match server_rx.recv().await {
+            Some(Ok(source)) => {
+                // We'll send ServerConnected with total_size from stream_video_data after opening the stream
+                Some(source)
+            }
+            Some(Err(error)) => {
+                let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
+                None
+            }
+            None => {
+                let _ = stream_tx.send(StreamEvent::StreamError {
+                    stream_id,
+                    error: "Server initialization failed".to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    async fn stream_video_data(
+        stream_id: StreamId,
+        source: Arc<dyn DataSource>,
+        address: String,
+        options: StreamOptions,
+        stream_tx: mpsc::UnboundedSender<StreamEvent>,
+    ) {
+        // Resuming by skipping bytes plays the rest of the data as if it started there
+        let source = match options.start_offset {
+            0 => source,
+            offset => Arc::new(OffsetSource::new(source, offset)) as Arc<dyn DataSource>,
+        };
+
+        let data_stream = match source.open(&address).await {
+            Ok(stream) => stream,
+            Err(error) => {
+                let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
+                return;
+            }
+        };
+
+        // Get total file size from the chunk stream
+        let total_size = data_stream.data_size();
+        println!(
+            "Total file size: {} bytes ({:.1} MB)",
+            total_size,
+            total_size as f64 / (1024.0 * 1024.0)
+        );
+
+        // Send ServerConnected event with total size
+        let _ = stream_tx.send(StreamEvent::ServerConnected {
+            stream_id,
+            total_size,
+        });
+
+        let outcome = match Self::process_stream_with_delayed_pipeline(
+            stream_id,
+            source.as_ref(),
+            &address,
+            data_stream,
+            total_size,
+            &options,
+            &stream_tx,
+        )
+        .await
+        {
+            Ok(Some(outcome)) => outcome,
+            Ok(None) => return,
+            Err(error) => {
+                let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
+                return;
+            }
+        };
+
+        // Keep serving the pipeline's seek requests for as long as the stream is alive
+        if let Err(error) = Self::serve_seek_requests(
+            stream_id,
+            source.as_ref(),
+            &address,
+            &outcome.video_streamer,
+            outcome.seek_offset,
+            outcome.tail,
+            outcome.received,
+            &options,
+            &stream_tx,
+        )
+        .await
+        {
+            let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
+        }
+    }
+
+    async fn process_stream_with_delayed_pipeline(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        stream: ChunkStream,
+        total_size: usize,
+        options: &StreamOptions,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<Option<LinearOutcome>, String> {
+        let mut buffer = Vec::new();
+        // Decided once the container header has arrived
+        let mut prebuffer: Option<PrebufferTarget> = None;
+        let mut trailing_moov = None;
+
+        let mut video_streamer: Option<Arc<VideoStreamer>> = None;
+
+        let mut playback_started = false;
+        let mut bytes_pushed = 0;
+
+        println!("Starting prebuffering - reading the container header to size the prebuffer");
+
+        let mut chunks = BlockingChunks::new(stream);
+        let mut bytes_received = 0;
+        let mut attempt = 0;
+
+        while let Some(chunk_result) = chunks.next().await {
+            if options.cancelled.load(Ordering::Relaxed) {
+                println!("Stream {stream_id} stopped, ending the download");
+                return Ok(None);
+            }
+            let chunk = match chunk_result {
+                Ok(chunk) => chunk,
+                Err(error) => {
+                    // Resume from the last byte received with range reads
+                    attempt += 1;
+                    Self::wait_before_retry(stream_id, attempt, error, &options.retry, stream_tx)
+                        .await?;
+                    chunks = match source.open_ranged(address).await {
+                        Ok(reader) => BlockingChunks::new(RangeChunks::new(
+                            reader,
+                            bytes_received,
+                            RANGE_CHUNK_SIZE,
+                        )),
+                        Err(error) => BlockingChunks::new(std::iter::once(Err(error))),
+                    };
+                    continue;
+                }
+            };
+            attempt = 0;
+            bytes_received += chunk.len();
+
+            if !playback_started {
+                // First phase: collect data until we have enough for reliable format detection
+                buffer.extend_from_slice(&chunk);
+
+                // Log progress every 10MB to reduce log spam
+                if buffer.len() % (10 * 1024 * 1024) < chunk.len() {
+                    println!(
+                        "Prebuffering progress: {}MB collected",
+                        buffer.len() / (1024 * 1024)
+                    );
+                }
+
+                if prebuffer.is_none() && buffer.len() >= container::SNIFF_SIZE {
+                    trailing_moov = container::mp4_trailing_moov(&buffer, total_size)
+                        .filter(|offset| total_size - offset <= MAX_TRAILING_MOOV_SIZE);
+
+                    let target = PrebufferTarget::from_header(
+                        &buffer,
+                        total_size,
+                        options.prebuffer_seconds,
+                    );
+                    println!(
+                        "Prebuffer target: {} bytes ({:?}s of playback)",
+                        target.bytes, target.seconds
+                    );
+                    // Not shown for a trailing moov, which starts playback without prebuffering
+                    if trailing_moov.is_none() {
+                        let _ = stream_tx.send(StreamEvent::PrebufferTarget { stream_id, target });
+                    }
+                    prebuffer = Some(target);
+                }
+
+                // Start playback once we hit the prebuffer target OR when we have all the data (whichever comes first)
+                if let Some(target) = prebuffer.filter(|target| buffer.len() >= target.bytes) {
+                    println!(
+                        "✅ Reached {} bytes prebuffer target! Creating video pipeline and starting playback",
+                        target.bytes
+                    );
+
+                    // Create pipeline and start playback
+                    let streamer = Self::start_video_streamer(
+                        stream_id, &buffer, total_size, options, stream_tx,
+                    )?;
+
+                    video_streamer = Some(streamer);
+                    playback_started = true;
+                    bytes_pushed = buffer.len();
+                    buffer.clear(); // Free the buffer memory
+                }
+            } else {
+                // Second phase: continue streaming remaining chunks to the pipeline
+                if let Some(ref streamer) = video_streamer {
+                    Self::push_chunk_to_streamer(&chunk, streamer)?;
+                    bytes_pushed += chunk.len();
+                }
+            }
+
+            if stream_tx
+                .send(StreamEvent::ChunkReceived {
+                    stream_id,
+                    size: chunk.len(),
+                })
+                .is_err()
+            {
+                break;
+            }
+
+            // The MP4 can't be decoded before its moov arrives, so fetch it now rather than last
+            if let Some(moov_offset) = trailing_moov.filter(|_| !playback_started) {
+                return Self::start_with_trailing_moov(
+                    stream_id,
+                    source,
+                    address,
+                    &buffer,
+                    moov_offset,
+                    total_size,
+                    options,
+                    stream_tx,
+                )
+                .await
+                .map(Some);
+            }
+
+            // The user seeked elsewhere, stop the linear download and fetch from the new offset
+            if let Some(streamer) = video_streamer.clone() {
+                if let Some(offset) = streamer.take_seek_request() {
+                    if offset as usize != bytes_pushed {
+                        println!(
+                            "Seek to byte {offset} during download, switching to range fetching"
+                        );
+                        return Ok(Some(LinearOutcome {
+                            video_streamer: streamer,
+                            seek_offset: Some(offset as usize),
+                            tail: None,
+                            received: bytes_received,
+                        }));
+                    }
+                }
+            }
+        }
+
+        // Handle case where total file size is less than the prebuffer target
+        if !playback_started && !buffer.is_empty() {
+            println!(
+                "✅ File smaller than the prebuffer target - creating video pipeline with {}MB of data",
+                buffer.len() / (1024 * 1024)
+            );
+
+            let streamer =
+                Self::start_video_streamer(stream_id, &buffer, total_size, options, stream_tx)?;
+            video_streamer = Some(streamer);
+        }
+
+        // Signal end of stream and completion
+        let Some(streamer) = video_streamer else {
+            return Ok(None);
+        };
+
+        println!("All chunks processed, signaling end of stream");
+        if let Err(e) = streamer.signal_end_of_stream() {
+            return Err(format!("Failed to signal end of stream: {e}"));
         }
+        println!("End of stream signaled successfully");
+
+        let _ = stream_tx.send(StreamEvent::StreamComplete { stream_id });
+        println!("StreamComplete event sent to UI");
+
+        Ok(Some(LinearOutcome {
+            video_streamer: streamer,
+            seek_offset: None,
+            tail: None,
+            received: bytes_received,
+        }))
     }
 
-    async fn stream_video_data(
+    /// Start an MP4 whose moov box comes after the media data by fetching the trailing boxes first,
+    /// the demuxer seeks to them from the header and then back to the media data
+    #[allow(clippy::too_many_arguments)]
+    async fn start_with_trailing_moov(
         stream_id: StreamId,
-        server: Server,
-        address: String,
-        stream_tx: mpsc::UnboundedSender<StreamEvent>,
-    ) {
-        let data_stream = match server.stream_data(&address).await {
-            Ok(stream) => stream,
-            Err(error) => {
+        source: &dyn DataSource,
+        address: &str,
+        head: &[u8],
+        moov_offset: usize,
+        total_size: usize,
+        options: &StreamOptions,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<LinearOutcome, String> {
+        println!("Stream {stream_id} is not fast start, fetching the boxes after byte {moov_offset} first");
+        let tail = Self::read_range_with_retry(
+            stream_id,
+            source,
+            address,
+            moov_offset,
+            total_size - moov_offset,
+            &options.retry,
+            stream_tx,
+        )
+        .await?;
+        let _ = stream_tx.send(StreamEvent::ChunkReceived {
+            stream_id,
+            size: tail.len(),
+        });
+
+        let video_streamer =
+            Self::start_video_streamer(stream_id, head, total_size, options, stream_tx)?;
+        Ok(LinearOutcome {
+            video_streamer,
+            seek_offset: None,
+            tail: Some((moov_offset, tail)),
+            received: head.len(),
+        })
+    }
+
+    async fn read_range_with_retry(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        start: usize,
+        len: usize,
+        retry: &RetryPolicy,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<bytes::Bytes, String> {
+        let mut attempt = 0;
+        loop {
+            let result = match source.open_ranged(address).await {
+                Ok(reader) => {
+                    BlockingRangeReader::new(reader)
+                        .read_range(start, len)
+                        .await
+                }
+                Err(error) => Err(error),
+            };
+            match result {
+                Ok(data) => return Ok(data),
+                Err(error) => {
+                    attempt += 1;
+                    Self::wait_before_retry(stream_id, attempt, error, retry, stream_tx).await?;
+                }
+            }
+        }
+    }
+
+    fn start_video_streamer(
+        stream_id: StreamId,
+        buffer: &[u8],
+        total_size: usize,
+        options: &StreamOptions,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<Arc<VideoStreamer>, String> {
+        // Forward pipeline bus events to the UI
+        let event_tx = stream_tx.clone();
+        let streamer = VideoStreamer::new(
+            total_size,
+            options.video_output,
+            &options.pipeline,
+            move |event| {
+                let _ = event_tx.send(StreamEvent::from_pipeline_event(stream_id, event));
+            },
+        )
+        .map_err(|e| format!("Failed to create video streamer: {}", e))?;
+
+        // Hold queue items fetched ahead until their turn
+        if options.start_paused {
+            streamer.pause()?;
+        }
+
+        // Drop the initial seek to offset 0 issued when the pipeline starts
+        let _ = streamer.take_seek_request();
+
+        println!(
+            "VideoStreamer created, pushing initial buffer of {}MB",
+            buffer.len() / (1024 * 1024)
+        );
+        Self::push_chunk_to_streamer(buffer, &streamer)?;
+
+        // Hand the video streamer to the UI to keep it alive and control playback
+        let streamer = Arc::new(streamer);
+        let _ = stream_tx.send(StreamEvent::VideoStreamerReady {
+            stream_id,
+            video_streamer: streamer.clone(),
+        });
+
+        Ok(streamer)
+    }
+
+    /// Report a failed fetch and wait out its backoff, or give up once the retries are used
+    async fn wait_before_retry(
+        stream_id: StreamId,
+        attempt: u32,
+        error: String,
+        policy: &RetryPolicy,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<(), String> {
+        if attempt > policy.max_retries {
+            if policy.max_retries == 0 {
+                return Err(error);
+            }
+            return Err(format!(
+                "{error} (gave up after {} retries)",
+                policy.max_retries
+            ));
+        }
+
+        let backoff = policy.backoff(attempt);
+        println!(
+            "Stream {stream_id} fetch failed: {error}, retry {attempt}/{} in {backoff:?}",
+            policy.max_retries
+        );
+        let _ = stream_tx.send(StreamEvent::Retrying {
+            stream_id,
+            attempt,
+            max_retries: policy.max_retries,
+            error,
+        });
+
+        tokio::time::sleep(backoff).await;
+        Ok(())
+    }
+
+    /// Feed the pipeline from the byte offsets it seeks to, using random access reads
+    #[allow(clippy::too_many_arguments)]
+    async fn serve_seek_requests(
+        stream_id: StreamId,
+        source: &dyn DataSource,
+        address: &str,
+        video_streamer: &VideoStreamer,
+        mut seek_offset: Option<usize>,
+        tail: Option<(usize, bytes::Bytes)>,
+        mut received: usize,
+        options: &StreamOptions,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<(), String> {
+        let mut download_complete = seek_offset.is_none() && tail.is_none();
+        let mut range_reader = None;
+
+        loop {
+            let mut position = match seek_offset.take() {
+                Some(offset) => offset,
+                None => video_streamer.wait_for_seek_request().await as usize,
+            };
+
+            // Opened on the first seek, retried like the reads themselves
+            let reader = match range_reader.take() {
+                Some(reader) => reader,
+                None => {
+                    let retry = &options.retry;
+                    Self::open_ranged_with_retry(stream_id, source, address, retry, stream_tx)
+                        .await?
+                }
+            };
+            let reader = &*range_reader.insert(reader);
+
+            println!("Stream {stream_id} fetching from byte offset {position}");
+            let mut attempt = 0;
+            // Whether this pass served the trailing boxes, and whether it read anything before them
+            let mut served_from_tail = false;
+            let mut read_media_data = false;
+            // The size of adaptive manifests changes as renditions are picked
+            while position < reader.data_size() {
+                if options.cancelled.load(Ordering::Relaxed) {
+                    println!("Stream {stream_id} stopped, no longer serving seeks");
+                    return Ok(());
+                }
+                if let Some(offset) = video_streamer.take_seek_request() {
+                    println!("Stream {stream_id} seeked to byte offset {offset}");
+                    position = offset as usize;
+                    served_from_tail = false;
+                    read_media_data = false;
+                    continue;
+                }
+
+                let tail_chunk = tail.as_ref().and_then(|(offset, data)| {
+                    let start = position.checked_sub(*offset)?;
+                    (start < data.len())
+                        .then(|| data.slice(start..(start + RANGE_CHUNK_SIZE).min(data.len())))
+                });
+                let from_tail = tail_chunk.is_some();
+                served_from_tail |= from_tail;
+
+                let chunk = match tail_chunk {
+                    Some(chunk) => chunk,
+                    None => match reader.read_range(position, RANGE_CHUNK_SIZE).await {
+                        Ok(chunk) => {
+                            read_media_data = true;
+                            chunk
+                        }
+                        Err(error) => {
+                            attempt += 1;
+                            Self::wait_before_retry(
+                                stream_id,
+                                attempt,
+                                error,
+                                &options.retry,
+                                stream_tx,
+                            )
+                            .await?;
+                            continue;
+                        }
+                    },
+                };
+                attempt = 0;
+                if chunk.is_empty() {
+                    break;
+                }
+                Self::push_chunk_to_streamer(&chunk, video_streamer)?;
+                let end = position + chunk.len();
+
+                // Bytes read again after seeking back were already counted
+                if !download_complete && !from_tail && end > received {
+                    let _ = stream_tx.send(StreamEvent::ChunkReceived {
+                        stream_id,
+                        size: end - received.max(position),
+                    });
+                    received = end;
+                }
+                position = end;
+            }
+
+            // A seek may have arrived while pushing the last range
+            if let Some(offset) = video_streamer.take_seek_request() {
+                seek_offset = Some(offset as usize);
+                continue;
+            }
+
+            // After reading only the trailing moov the demuxer seeks back to the media data,
+            // a pass that read the media data through the trailing boxes has reached the end
+            if served_from_tail && !read_media_data {
+                continue;
+            }
+
+            video_streamer.signal_end_of_stream()?;
+            if !download_complete {
+                download_complete = true;
+                let _ = stream_tx.send(StreamEvent::StreamComplete { stream_id });
+            }
+        }
+    }
+
+    fn pick_and_upload_file(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Video", &["mp4", "mkv", "webm", "mov", "avi"])
+            .add_filter("All files", &["*"])
+            .pick_file()
+        else {
+            return;
+        };
+
+        let stream_id = self.next_stream_id;
+        self.next_stream_id += 1;
+
+        let environment = self.selected_env.clone();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.display().to_string());
+
+        let stream_info = StreamInfo {
+            id: stream_id,
+            address: file_name,
+            environment: environment.clone(),
+            status: StreamStatus::Uploading {
+                stage: "Reading file".to_string(),
+                total_size: 0,
+            },
+            created_at: std::time::Instant::now(),
+            buffering_percent: None,
+            cache_stats: None,
+            retrying: None,
+            prebuffer: None,
+            renditions: Vec::new(),
+            metadata: None,
+            resumed_from: None,
+        };
+        self.streams.insert(stream_id, stream_info);
+
+        let with_metadata = self.upload_metadata;
+        let to_channel = self.upload_to_channel;
+        let pool = self.pool.clone();
+        let stream_tx = self.stream_sender.clone();
+        let task = tokio::spawn(async move {
+            if let Err(error) = Self::run_upload_task(
+                stream_id,
+                path,
+                environment,
+                &pool,
+                with_metadata,
+                to_channel,
+                &stream_tx,
+            )
+            .await
+            {
                 let _ = stream_tx.send(StreamEvent::StreamError { stream_id, error });
-                return;
             }
+        });
+        self.stream_tasks.insert(stream_id, task);
+
+        println!("Started upload {stream_id}");
+    }
+
+    async fn run_upload_task(
+        stream_id: StreamId,
+        path: std::path::PathBuf,
+        environment: String,
+        pool: &ServerPool,
+        with_metadata: bool,
+        to_channel: bool,
+        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
+    ) -> Result<(), String> {
+        let send_stage = |stage: &str, total_size: usize| {
+            let _ = stream_tx.send(StreamEvent::UploadProgress {
+                stream_id,
+                stage: stage.to_string(),
+                total_size,
+            });
+        };
+
+        let data = tokio::fs::read(&path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let data = bytes::Bytes::from(data);
+        let total_size = data.len();
+
+        send_stage("Connecting to network", total_size);
+        let server = pool.get(&environment).await?;
+        let wallet = server.load_wallet()?;
+
+        send_stage("Estimating cost", total_size);
+        let estimate = server.estimate_upload_cost(data.clone()).await?;
+
+        send_stage(
+            &format!("Uploading (estimated cost {estimate})"),
+            total_size,
+        );
+        let (cost, address) = server.upload_public(data.clone(), &wallet).await?;
+
+        // The metadata address is the one to share, it points at the video
+        let (cost, address) = if with_metadata {
+            send_stage("Uploading metadata", total_size);
+            let title = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| address.to_hex());
+            let metadata = VideoMetadata::for_upload(&title, &data, address.to_hex());
+            let (metadata_cost, metadata_address) =
+                server.upload_public(metadata.to_bytes(), &wallet).await?;
+            (format!("{cost} + {metadata_cost}"), metadata_address)
+        } else {
+            (cost.to_string(), address)
+        };
+
+        if to_channel {
+            send_stage("Publishing to channel", total_size);
+            let owner = server::load_channel_key()?;
+            let channel = server.publish_to_channel(&owner, &address, &wallet).await?;
+            let _ = stream_tx.send(StreamEvent::ChannelPublished { channel });
+        }
+
+        let _ = stream_tx.send(StreamEvent::UploadComplete {
+            stream_id,
+            address: address.to_hex(),
+            cost,
+        });
+        Ok(())
+    }
+
+    fn push_chunk_to_streamer(chunk: &[u8], video_streamer: &VideoStreamer) -> Result<(), String> {
+        println!(
+            "Received chunk of size: {} bytes, pushing to video streamer",
+            chunk.len()
+        );
+
+        video_streamer
+            .push_chunk(chunk.to_vec())
+            .map_err(|e| format!("Failed to push chunk to video streamer: {e}"))?;
+
+        println!("Successfully pushed chunk to video streamer");
+        Ok(())
+    }
+
+    fn apply_stream_action(&mut self, stream_id: StreamId, action: StreamAction) {
+        match action {
+            StreamAction::Pause | StreamAction::Resume => {
+                let Some(streamer) = self.video_streamers.get(&stream_id) else {
+                    return;
+                };
+                let paused = matches!(action, StreamAction::Pause);
+                let result = if paused {
+                    streamer.pause()
+                } else {
+                    streamer.resume()
+                };
+
+                match result {
+                    Ok(()) => {
+                        if let Some(stream) = self.streams.get_mut(&stream_id) {
+                            stream.status.set_paused(paused);
+                        }
+                    }
+                    Err(e) => println!("Stream {stream_id} {action:?} failed: {e}"),
+                }
+            }
+            StreamAction::Seek(position) => {
+                let Some(streamer) = self.video_streamers.get(&stream_id) else {
+                    return;
+                };
+                if let Err(e) = streamer.seek_to(position) {
+                    println!("Stream {stream_id} seek failed: {e}");
+                    return;
+                }
+
+                // Seeking back after the end plays again
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    if let StreamStatus::PlaybackFinished {
+                        total_bytes_received,
+                        chunks_received,
+                        stats,
+                    } = &stream.status
+                    {
+                        stream.status = StreamStatus::Completed {
+                            total_bytes_received: *total_bytes_received,
+                            chunks_received: *chunks_received,
+                            paused: false,
+                            stats: stats.clone(),
+                        };
+                    }
+                }
+            }
+            StreamAction::Stop => {
+                println!("Stopping stream {stream_id}");
+                // Stopping the queue's stream stops the queue too
+                if self.queue_stream == Some(stream_id) {
+                    self.queue_stream = None;
+                }
+                self.pending_resumes.remove(&stream_id);
+                self.teardown_stream(stream_id);
+                if let Some(stream) = self.streams.get_mut(&stream_id) {
+                    stream.status = StreamStatus::Stopped;
+                }
+                self.save_history();
+            }
+        }
+    }
+
+    /// Add items to the queue, those without a network playing from `network`, and start the
+    /// first of them if the queue was idle
+    fn enqueue(&mut self, items: Vec<PlaylistItem>, network: String) {
+        let mut first = None;
+        for mut item in items {
+            item.network.get_or_insert_with(|| network.clone());
+            let index = self.queue.add(item);
+            first.get_or_insert(index);
+        }
+
+        if let (None, Some(first)) = (self.queue_stream, first) {
+            self.queue.play(first);
+            self.start_queue_item();
+        } else {
+            self.sync_queue_prefetch();
+        }
+    }
+
+    /// Queue item played or prefetched by a stream
+    fn queue_item_of(&self, stream_id: StreamId) -> Option<usize> {
+        if self.queue_stream == Some(stream_id) {
+            return self.queue.current();
+        }
+        self.queue_prefetch
+            .filter(|(_, id)| *id == stream_id)
+            .map(|(index, _)| index)
+    }
+
+    /// Play the queue's current item, taking over its prefetched stream when there is one
+    fn start_queue_item(&mut self) {
+        let Some(index) = self.queue.current() else {
+            self.queue_stream = None;
+            return;
         };
 
-        // Get total file size from DataStream
-        let total_size = data_stream.data_size() as usize;
-        println!(
-            "Total file size: {} bytes ({:.1} MB)",
-            total_size,
-            total_size as f64 / (1024.0 * 1024.0)
-        );
+        match self.queue_prefetch.take() {
+            Some((prefetched, stream_id)) if prefetched == index => {
+                println!("Playing prefetched queue item {index} from stream {stream_id}");
+                self.queue_stream = Some(stream_id);
+                self.apply_stream_action(stream_id, StreamAction::Resume);
+            }
+            prefetch => {
+                if let Some((_, stream_id)) = prefetch {
+                    self.discard_stream(stream_id);
+                }
+                let (address, network) = self.queue_item_source(index);
+                let stream_id = self.start_stream(address, network, false, 0);
+                self.queue_stream = Some(stream_id);
+            }
+        }
+    }
+
+    /// Address of a queue item and the network it was queued for
+    fn queue_item_source(&self, index: usize) -> (String, String) {
+        let item = &self.queue.items()[index];
+        let network = item
+            .network
+            .clone()
+            .unwrap_or_else(|| self.selected_env.clone());
+        (item.address.clone(), network)
+    }
+
+    /// Move on to the next queue item once the current one has ended
+    fn advance_queue(&mut self) {
+        self.queue_stream = None;
+        if self.queue.advance().is_some() {
+            self.start_queue_item();
+        } else {
+            println!("Queue finished");
+            self.discard_queue_prefetch();
+        }
+    }
+
+    fn skip_failed_queue_stream(&mut self, stream_id: StreamId) {
+        if self.queue_stream == Some(stream_id) {
+            println!("Queue item failed, skipping to the next one");
+            self.advance_queue();
+        } else if self.queue_prefetch.is_some_and(|(_, id)| id == stream_id) {
+            // Retried from scratch when its turn comes
+            self.queue_prefetch = None;
+        }
+    }
 
-        // Send ServerConnected event with total size
-        let _ = stream_tx.send(StreamEvent::ServerConnected {
-            stream_id,
-            total_size,
-        });
+    /// Start the next queue item paused once the current one is downloaded, so it plays without prebuffering
+    fn prefetch_next_queue_item(&mut self) {
+        let Some(next) = self.queue.peek_next() else {
+            return;
+        };
+        if self.queue_prefetch.is_some() || Some(next) == self.queue.current() {
+            return;
+        }
 
-        // Convert DataStream to iterator for processing
-        let stream_iter = data_stream.map(|chunk_result| chunk_result.map_err(|e| e.to_string()));
+        let (address, network) = self.queue_item_source(next);
+        println!("Prefetching queue item {next} ({address})");
+        let stream_id = self.start_stream(address, network, true, 0);
+        self.queue_prefetch = Some((next, stream_id));
+    }
 
-        if let Err(e) = Self::process_stream_with_delayed_pipeline(
-            stream_id,
-            stream_iter,
-            total_size,
-            &stream_tx,
-        ) {
-            let _ = stream_tx.send(StreamEvent::StreamError {
-                stream_id,
-                error: e,
-            });
+    /// Drop the prefetched stream once it is no longer the next item
+    fn sync_queue_prefetch(&mut self) {
+        let next = self.queue.peek_next();
+        if self
+            .queue_prefetch
+            .is_some_and(|(index, _)| Some(index) != next)
+        {
+            self.discard_queue_prefetch();
         }
+    }
 
-        // StreamComplete will be sent from process_stream_with_delayed_pipeline with VideoStreamer
+    fn discard_queue_prefetch(&mut self) {
+        if let Some((_, stream_id)) = self.queue_prefetch.take() {
+            self.discard_stream(stream_id);
+        }
     }
 
-    fn process_stream_with_delayed_pipeline(
-        stream_id: StreamId,
-        stream: impl Iterator<Item = Result<bytes::Bytes, String>>,
-        _total_size: usize,
-        stream_tx: &mpsc::UnboundedSender<StreamEvent>,
-    ) -> Result<(), String> {
-        let mut buffer = Vec::new();
-        const PREBUFFER_SIZE: usize = 10 * 1024 * 1024; // 10MB
+    /// Record the position of playing streams and seek resumed ones once their pipeline can
+    fn update_history(&mut self) {
+        let Some(history) = &mut self.history else {
+            return;
+        };
 
-        let mut video_streamer: Option<VideoStreamer> = None;
+        let mut resumed = Vec::new();
+        for (stream_id, streamer) in &self.video_streamers {
+            let Some(stream) = self.streams.get(stream_id) else {
+                continue;
+            };
+            // Stand-in addresses of private data mean nothing once the app restarts
+            if data_map::is_private(&stream.address) {
+                continue;
+            }
 
-        let mut playback_started = false;
+            if let Some(entry) = self.pending_resumes.get(stream_id) {
+                if streamer.duration_seconds().is_some() {
+                    resumed.push((*stream_id, streamer.seek_to(entry.position)));
+                }
+                continue;
+            }
 
-        println!(
-            "Starting prebuffering - will collect up to {}MB before starting video pipeline",
-            PREBUFFER_SIZE / (1024 * 1024)
-        );
+            // Finished videos keep their last position, which mustn't undo the completion
+            let finished = matches!(stream.status, StreamStatus::PlaybackFinished { .. });
+            let prefetched = self.queue_prefetch.is_some_and(|(_, id)| id == *stream_id);
+            if finished || prefetched || stream.status.is_paused() {
+                continue;
+            }
+            let Some(position) = streamer.position_seconds().filter(|p| *p > 0.0) else {
+                continue;
+            };
 
-        for chunk_result in stream {
-            let chunk = chunk_result?;
+            let duration = streamer.duration_seconds();
+            let (position, duration, offset) = match stream.resumed_from {
+                // Positions of skipped data start at zero, the byte rate comes from before the skip
+                Some((base_position, base_offset)) => {
+                    let byte_rate = base_offset as f64 / base_position;
+                    let offset = base_offset + (position * byte_rate) as usize;
+                    (base_position + position, None, Some(offset))
+                }
+                None => {
+                    let offset = duration
+                        .filter(|duration| *duration > 0.0)
+                        .zip(stream.status.data_size())
+                        .map(|(duration, size)| (position / duration * size as f64) as usize);
+                    (position, duration, offset)
+                }
+            };
+            history.record_position(
+                &stream.address,
+                &stream.environment,
+                position,
+                duration,
+                offset,
+            );
+            if let Some(metadata) = &stream.metadata {
+                history.set_title(&stream.address, &stream.environment, &metadata.title);
+            }
+        }
 
-            if !playback_started {
-                // First phase: collect data until we have enough for reliable format detection
-                buffer.extend_from_slice(&chunk);
+        if self.last_history_save.elapsed() >= HISTORY_SAVE_INTERVAL {
+            self.save_history();
+        }
 
-                // Log progress every 10MB to reduce log spam
-                if buffer.len() % (10 * 1024 * 1024) < chunk.len() {
+        for (stream_id, result) in resumed {
+            let Some(entry) = self.pending_resumes.remove(&stream_id) else {
+                continue;
+            };
+            match result {
+                Ok(()) => println!("Stream {stream_id} resumed at {:.0}s", entry.position),
+                Err(e) => {
                     println!(
-                        "Prebuffering progress: {}MB collected",
-                        buffer.len() / (1024 * 1024)
+                        "Stream {stream_id} can't seek to resume ({e}), skipping bytes instead"
                     );
+                    self.discard_stream(stream_id);
+                    self.resume_by_skipping(entry);
                 }
+            }
+        }
+    }
 
-                // Start playback once we hit PREBUFFER_SIZE OR when we have all the data (whichever comes first)
-                if buffer.len() >= PREBUFFER_SIZE {
-                    println!("✅ Reached {PREBUFFER_SIZE}MB prebuffer limit! Creating video pipeline and starting playback");
+    /// Play a history entry from its saved position, seeking once the pipeline allows it
+    fn resume_from_history(&mut self, entry: HistoryEntry) {
+        if entry.resume_position().is_none() {
+            self.start_stream(entry.address, entry.environment, false, 0);
+            return;
+        }
 
-                    // Create pipeline and start playback
-                    let streamer = VideoStreamer::new()
-                        .map_err(|e| format!("Failed to create video streamer: {}", e))?;
+        // Without a known duration the pipeline won't seek, so start from the saved byte offset
+        if entry.duration.is_none() && entry.offset.is_some() {
+            self.resume_by_skipping(entry);
+            return;
+        }
 
-                    println!(
-                        "VideoStreamer created, pushing initial buffer of {}MB",
-                        buffer.len() / (1024 * 1024)
-                    );
-                    Self::push_chunk_to_streamer(&buffer, &streamer)?;
+        let stream_id =
+            self.start_stream(entry.address.clone(), entry.environment.clone(), false, 0);
+        self.pending_resumes.insert(stream_id, entry);
+    }
 
-                    // Signal that video streamer is ready
-                    let _ = stream_tx.send(StreamEvent::VideoStreamerReady { stream_id });
+    fn resume_by_skipping(&mut self, entry: HistoryEntry) {
+        let Some(offset) = entry.offset.filter(|offset| *offset > 0) else {
+            println!(
+                "No byte offset saved for {}, playing from the start",
+                entry.address
+            );
+            self.start_stream(entry.address, entry.environment, false, 0);
+            return;
+        };
 
-                    video_streamer = Some(streamer);
-                    playback_started = true;
-                    buffer.clear(); // Free the buffer memory
+        let stream_id = self.start_stream(entry.address, entry.environment, false, offset);
+        if let Some(stream) = self.streams.get_mut(&stream_id) {
+            stream.resumed_from = Some((entry.position, offset));
+        }
+    }
+
+    fn apply_history_action(&mut self, action: HistoryAction) {
+        match action {
+            HistoryAction::Resume(entry) => self.resume_from_history(entry),
+            HistoryAction::Play(entry) => {
+                self.start_stream(entry.address, entry.environment, false, 0);
+            }
+            HistoryAction::Remove(entry) => {
+                if let Some(history) = &mut self.history {
+                    history.remove(&entry.address, &entry.environment);
                 }
-            } else {
-                // Second phase: continue streaming remaining chunks to the pipeline
-                if let Some(ref streamer) = video_streamer {
-                    Self::push_chunk_to_streamer(&chunk, streamer)?;
+            }
+            HistoryAction::Clear => {
+                if let Some(history) = &mut self.history {
+                    history.clear();
                 }
             }
+        }
+        self.save_history();
+    }
 
-            if stream_tx
-                .send(StreamEvent::ChunkReceived {
-                    stream_id,
-                    size: chunk.len(),
-                })
-                .is_err()
-            {
-                break;
+    fn save_history(&mut self) {
+        self.last_history_save = std::time::Instant::now();
+        if let Some(Err(e)) = self.history.as_mut().map(WatchHistory::save) {
+            println!("Failed to save watch history: {e}");
+        }
+    }
+
+    /// Tear down a stream and remove its card
+    fn discard_stream(&mut self, stream_id: StreamId) {
+        self.teardown_stream(stream_id);
+        self.streams.remove(&stream_id);
+    }
+
+    /// Stop the queue's stream when the user picks another item
+    fn stop_queue_stream(&mut self) {
+        if let Some(stream_id) = self.queue_stream {
+            self.apply_stream_action(stream_id, StreamAction::Stop);
+        }
+    }
+
+    /// Replace the card of a stream that turned out to be a playlist with its items in the queue
+    fn load_playlist(&mut self, stream_id: StreamId, playlist: Playlist) {
+        println!(
+            "Loaded playlist {:?} with {} items",
+            playlist.name,
+            playlist.items.len()
+        );
+        // The items play from the network the playlist was found on
+        let network = self
+            .streams
+            .remove(&stream_id)
+            .map_or_else(|| self.selected_env.clone(), |stream| stream.environment);
+
+        if self.queue_stream == Some(stream_id) {
+            // A queued playlist address is replaced by its items
+            self.queue_stream = None;
+            if let Some(index) = self.queue.current() {
+                self.discard_queue_prefetch();
+                self.queue.remove(index);
             }
+        } else if self.queue_prefetch.is_some_and(|(_, id)| id == stream_id) {
+            self.queue_prefetch = None;
         }
 
-        // Handle case where total file size is less than PREBUFFER_SIZE
-        if !playback_started && !buffer.is_empty() {
-            println!(
-                "✅ File smaller than {PREBUFFER_SIZE}MB - creating video pipeline with {}MB of data",
-                buffer.len() / (1024 * 1024)
-            );
+        self.queue_status = Some(format!("Loaded playlist {}", playlist.name));
+        self.playlist_name = playlist.name;
+        self.enqueue(playlist.items, network);
+    }
 
-            let streamer = VideoStreamer::new()
-                .map_err(|e| format!("Failed to create video streamer: {}", e))?;
+    fn apply_queue_action(&mut self, action: QueueAction) {
+        match action {
+            QueueAction::Play(index) => {
+                self.stop_queue_stream();
+                self.queue.play(index);
+                self.start_queue_item();
+            }
+            QueueAction::Remove(index) => {
+                // Item indexes shift, so the prefetched stream can't be matched anymore
+                self.discard_queue_prefetch();
+                self.queue.remove(index);
+            }
+            QueueAction::Next => {
+                self.stop_queue_stream();
+                self.advance_queue();
+            }
+            QueueAction::ToggleShuffle => {
+                let seed = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+                self.queue.set_shuffle(!self.queue.is_shuffled(), seed);
+                self.sync_queue_prefetch();
+            }
+            QueueAction::CycleRepeat => {
+                self.queue.repeat = self.queue.repeat.cycle();
+                self.sync_queue_prefetch();
+            }
+            QueueAction::Clear => {
+                self.stop_queue_stream();
+                self.discard_queue_prefetch();
+                self.queue.clear();
+                self.queue_status = None;
+                self.playlist_name = DEFAULT_PLAYLIST_NAME.to_string();
+            }
+            QueueAction::Open => self.open_playlist_file(),
+            QueueAction::Save => self.save_playlist_file(),
+            QueueAction::Publish => self.publish_playlist(),
+        }
+    }
 
-            Self::push_chunk_to_streamer(&buffer, &streamer)?;
+    fn open_playlist_file(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Playlist", &["json"])
+            .pick_file()
+        else {
+            return;
+        };
 
-            // Signal that video streamer is ready
-            let _ = stream_tx.send(StreamEvent::VideoStreamerReady { stream_id });
+        match Playlist::load(&path) {
+            Ok(playlist) => {
+                self.queue_status = Some(format!("Opened {}", path.display()));
+                self.playlist_name = playlist.name;
+                let network = self.selected_env.clone();
+                self.enqueue(playlist.items, network);
+            }
+            Err(e) => self.queue_status = Some(e),
+        }
+    }
 
-            video_streamer = Some(streamer);
+    fn save_playlist_file(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Playlist", &["json"])
+            .set_file_name(format!("{}.json", self.playlist_name))
+            .save_file()
+        else {
+            return;
+        };
+
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| self.playlist_name.clone());
+        let (playlist, skipped) = self.queue.to_playlist(&name);
+        self.queue_status = Some(match playlist.save(&path) {
+            Ok(()) => {
+                self.playlist_name = name;
+                format!("Saved to {}{}", path.display(), private_items_note(skipped))
+            }
+            Err(e) => e,
+        });
+    }
+
+    /// Upload the queue as a public playlist document on the selected network
+    fn publish_playlist(&mut self) {
+        let (playlist, skipped) = self.queue.to_playlist(&self.playlist_name);
+        if playlist.items.is_empty() {
+            self.queue_status = Some(format!("Nothing to publish{}", private_items_note(skipped)));
+            return;
         }
+        let environment = self.selected_env.clone();
+        let pool = self.pool.clone();
+        let stream_tx = self.stream_sender.clone();
+        self.queue_status = Some("Publishing playlist...".to_string());
 
-        // Signal end of stream and completion
-        if let Some(streamer) = video_streamer {
-            println!("All chunks processed, signaling end of stream");
-            if let Err(e) = streamer.signal_end_of_stream() {
-                return Err(format!("Failed to signal end of stream: {e}"));
+        tokio::spawn(async move {
+            let result: Result<String, String> = async {
+                let server = pool.get(&environment).await?;
+                let wallet = server.load_wallet()?;
+                let (_, address) = server.upload_public(playlist.to_bytes(), &wallet).await?;
+                Ok(address.to_hex())
             }
-            println!("End of stream signaled successfully");
+            .await;
+            let _ = stream_tx.send(StreamEvent::PlaylistPublished { result, skipped });
+        });
+    }
 
-            // Send completion event to UI with VideoStreamer to keep it alive
-            let _ = stream_tx.send(StreamEvent::StreamComplete {
-                stream_id,
-                video_streamer: streamer,
-            });
-            println!("StreamComplete event sent to UI with VideoStreamer");
+    /// Follow the address in the address box as a channel on the selected network
+    fn subscribe_to_channel(&mut self) {
+        let address = self.address_input.trim().to_string();
+        let Some(subscriptions) = &mut self.subscriptions else {
+            self.subscribe_status = Some(Err("Channel subscriptions are disabled".to_string()));
+            return;
+        };
+
+        let status = match subscriptions.subscribe(&address, &self.selected_env) {
+            Ok(true) => {
+                // Poll right away so the new channel fills in
+                self.last_feed_poll = None;
+                Ok(format!("Subscribed to channel {address}"))
+            }
+            Ok(false) => Ok(format!("Already subscribed to channel {address}")),
+            Err(e) => Err(format!("Failed to subscribe to channel {address}: {e}")),
+        };
+        match &status {
+            Ok(message) | Err(message) => println!("{message}"),
         }
+        self.subscribe_status = Some(status);
+    }
 
-        Ok(())
+    /// Fetch the videos of every subscribed channel, with one client per network
+    fn poll_feed(&mut self) {
+        self.last_feed_poll = Some(std::time::Instant::now());
+        let Some(subscriptions) = &self.subscriptions else {
+            return;
+        };
+
+        let mut by_environment: HashMap<String, Vec<String>> = HashMap::new();
+        for channel in subscriptions.channels() {
+            by_environment
+                .entry(channel.environment.clone())
+                .or_default()
+                .push(channel.address.clone());
+        }
+
+        for (environment, channels) in by_environment {
+            let pool = self.pool.clone();
+            let stream_tx = self.stream_sender.clone();
+            tokio::spawn(async move {
+                let server = match pool.get(&environment).await {
+                    Ok(server) => server,
+                    Err(error) => {
+                        for channel in channels {
+                            let result = Err(error.clone());
+                            let _ = stream_tx.send(StreamEvent::ChannelPolled { channel, result });
+                        }
+                        return;
+                    }
+                };
+
+                for channel in channels {
+                    let result = server.channel_feed(&channel).await;
+                    let _ = stream_tx.send(StreamEvent::ChannelPolled { channel, result });
+                }
+            });
+        }
     }
 
-    fn push_chunk_to_streamer(chunk: &[u8], video_streamer: &VideoStreamer) -> Result<(), String> {
-        println!(
-            "Received chunk of size: {} bytes, pushing to video streamer",
-            chunk.len()
-        );
+    fn apply_feed_action(&mut self, action: FeedAction) {
+        let Some(subscriptions) = &mut self.subscriptions else {
+            return;
+        };
 
-        video_streamer
-            .push_chunk(chunk.to_vec())
-            .map_err(|e| format!("Failed to push chunk to video streamer: {e}"))?;
+        let result = match action {
+            FeedAction::Play {
+                channel,
+                video,
+                environment,
+            } => {
+                let result = subscriptions.mark_seen(&channel, std::slice::from_ref(&video));
+                self.address_input = video;
+                self.selected_env = environment;
+                self.connect_and_stream();
+                result
+            }
+            FeedAction::MarkSeen { channel } => {
+                let videos: Vec<String> = match self.feed.get(&channel) {
+                    Some(Ok(videos)) => videos.iter().map(|video| video.address.clone()).collect(),
+                    _ => Vec::new(),
+                };
+                subscriptions.mark_seen(&channel, &videos)
+            }
+            FeedAction::Unsubscribe { channel } => {
+                self.feed.remove(&channel);
+                subscriptions.unsubscribe(&channel)
+            }
+        };
 
-        println!("Successfully pushed chunk to video streamer");
-        Ok(())
+        if let Err(e) = result {
+            println!("Failed to update subscriptions: {e}");
+        }
+    }
+
+    /// Abort the download task and tear down the pipeline of a single stream
+    fn teardown_stream(&mut self, stream_id: StreamId) {
+        // A range read in flight finishes on its blocking thread, the flag stops any after it
+        if let Some(cancelled) = self.stream_cancels.remove(&stream_id) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+        if let Some(task) = self.stream_tasks.remove(&stream_id) {
+            task.abort();
+        }
+
+        if let Some(streamer) = self.video_streamers.remove(&stream_id) {
+            if let Err(e) = streamer.stop() {
+                println!("Stream {stream_id} failed to stop pipeline: {e}");
+            }
+        }
+        self.video_textures.remove(&stream_id);
+        self.thumbnail_textures.remove(&stream_id);
+        if let Some(stream) = self.streams.get_mut(&stream_id) {
+            stream.buffering_percent = None;
+        }
     }
 
     fn clear_all_streams(&mut self) {
         println!("Clearing all streams and VideoStreamers");
 
         // Abort all running streaming tasks
+        for (_, cancelled) in self.stream_cancels.drain() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
         for (stream_id, task) in self.stream_tasks.drain() {
             println!("Aborting streaming task for stream {}", stream_id);
             task.abort();
@@ -703,12 +3829,31 @@ impl AntubeApp {
         // Clear all streams
         self.streams.clear();
 
+        self.queue_stream = None;
+        self.queue_prefetch = None;
+        self.pending_resumes.clear();
+        self.pending_seeks.clear();
+        self.save_history();
+
         // Clear all VideoStreamers - this will stop all GStreamer pipelines
         self.video_streamers.clear();
+        self.video_textures.clear();
+        self.thumbnail_textures.clear();
 
         println!("All streams and VideoStreamers cleared");
     }
 
+    /// First and last characters of an address
+    fn format_short_address(&self, address: &str) -> String {
+        let chars: Vec<char> = address.chars().collect();
+        if chars.len() <= 16 {
+            return address.to_string();
+        }
+        let head: String = chars[..8].iter().collect();
+        let tail: String = chars[chars.len() - 6..].iter().collect();
+        format!("{head}…{tail}")
+    }
+
     fn format_data_size(&self, bytes: usize) -> String {
         const UNITS: &[&str] = &["B", "KB", "MB", "GB"];
         let mut size = bytes as f64;
@@ -725,6 +3870,17 @@ impl AntubeApp {
             format!("{:.1} {}", size, UNITS[unit_index])
         }
     }
+
+    fn format_duration(&self, seconds: f64) -> String {
+        let total = seconds.max(0.0) as u64;
+        let (hours, minutes, seconds) = (total / 3600, (total / 60) % 60, total % 60);
+
+        if hours > 0 {
+            format!("{hours}:{minutes:02}:{seconds:02}")
+        } else {
+            format!("{minutes}:{seconds:02}")
+        }
+    }
 }
 
 fn load_icon() -> egui::IconData {
@@ -744,11 +3900,66 @@ fn load_icon() -> egui::IconData {
 
 #[tokio::main]
 async fn main() -> eframe::Result<()> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+    if let Some(uri) = args.uri.take() {
+        args.address = Some(uri);
+    }
+
+    let config_path = match args.config.clone() {
+        Some(path) => Some(path),
+        None => match Config::default_path() {
+            Ok(path) => Some(path),
+            Err(e) => {
+                println!("Config file disabled: {e}");
+                None
+            }
+        },
+    };
+    let (file_config, mut config, config_error) = load_config(&args, config_path.as_deref());
+
+    if let Some(command) = args.command.take() {
+        if command.is_headless() {
+            if let Some(error) = config_error {
+                eprintln!("Invalid configuration: {error}");
+                std::process::exit(2);
+            }
+            let exit_code = cli::run(command, &config).await;
+            std::process::exit(exit_code);
+        }
+
+        // `play` without --no-gui opens the GUI streaming the address
+        if let cli::Command::Play { address, .. } = command {
+            args.address = Some(address);
+        }
+    }
+
+    if let Some(error) = &config_error {
+        println!("Invalid configuration, starting with the defaults: {error}");
+        config = Config::default();
+        args.apply_overrides(&mut config);
+        if config.validate().is_err() {
+            config = Config::default();
+        }
+    }
+
+    // Open the address in the running AnTube instead of a second window
+    if let Some(address) = &args.address {
+        let network = (args.network.is_some() || args.test).then_some(config.network.as_str());
+        let uri = handoff_uri(address, network).unwrap_or_else(|_| address.clone());
+        match instance::hand_off(&uri) {
+            Ok(true) => {
+                println!("Opened {} in the running AnTube", data_map::redact(address));
+                return Ok(());
+            }
+            Ok(false) => {}
+            Err(e) => println!("{e}"),
+        }
+    }
 
     println!(
         "Starting AnTube with network: {} and address: {:?}",
-        args.network, args.address
+        config.network,
+        args.address.as_deref().map(data_map::redact)
     );
 
     let options = eframe::NativeOptions {
@@ -762,6 +3973,254 @@ async fn main() -> eframe::Result<()> {
     eframe::run_native(
         "AnTube",
         options,
-        Box::new(|_cc| Box::new(AntubeApp::new(args))),
+        Box::new(|cc| {
+            let app = AntubeApp::new(args, config, file_config, config_path, config_error);
+            app.listen_for_uris(&cc.egui_ctx);
+            Box::new(app)
+        }),
     )
 }
+
+/// URI handing `input` to the running instance, naming the network given on the command line.
+/// Data map files go by their data map since its working directory may differ.
+fn handoff_uri(input: &str, network: Option<&str>) -> Result<String, String> {
+    let mut uri = AntubeUri::parse(input)?;
+    if let Some(data_map) = data_map::read_file(&uri.address)? {
+        uri.address = data_map;
+    }
+    if uri.network.is_none() {
+        uri.network = network.map(str::to_string);
+    }
+    Ok(uri.to_string())
+}
+
+/// Tells the user that private items were left out of a saved or published playlist
+fn private_items_note(skipped: usize) -> String {
+    match skipped {
+        0 => String::new(),
+        1 => ", left out 1 private item that only plays this session".to_string(),
+        n => format!(", left out {n} private items that only play this session"),
+    }
+}
+
+/// Config file settings, the same with the command line overrides applied, and why they are invalid if they are
+fn load_config(args: &Args, path: Option<&std::path::Path>) -> (Config, Config, Option<String>) {
+    let file_config = match path
+        .map(Config::load)
+        .unwrap_or_else(|| Ok(Config::default()))
+    {
+        Ok(config) => config,
+        Err(e) => return (Config::default(), Config::default(), Some(e)),
+    };
+    let mut config = file_config.clone();
+    args.apply_overrides(&mut config);
+    let error = config.validate().err();
+    (file_config, config, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use data_source::MemorySource;
+    use prebuffer::DEFAULT_PREBUFFER_SECONDS;
+
+    /// Options of a stream decoding without displaying, with `retry` as the retry policy
+    fn test_stream_options(retry: RetryPolicy) -> StreamOptions {
+        StreamOptions {
+            video_output: VideoOutput::Fake,
+            retry,
+            prebuffer_seconds: DEFAULT_PREBUFFER_SECONDS,
+            start_paused: false,
+            start_offset: 0,
+            pipeline: PipelineSettings::default(),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Source holding 16 bytes at "abc" whose stream fails after two 4 byte chunks
+    fn failing_source() -> Arc<dyn DataSource> {
+        let mut source = MemorySource::new(4);
+        source.insert_failing("abc", vec![0u8; 16], 2);
+        Arc::new(source)
+    }
+
+    /// ftyp, a 100KB mdat, then an 8 byte moov, with the offset of the moov
+    fn mp4_with_trailing_moov() -> (bytes::Bytes, usize) {
+        let mdat_size = 100 * 1024;
+        let mut data = vec![0, 0, 0, 16];
+        data.extend_from_slice(b"ftypisom\0\0\0\0");
+        data.extend_from_slice(&(mdat_size as u32).to_be_bytes());
+        data.extend_from_slice(b"mdat");
+        data.resize(16 + mdat_size, 0);
+        let moov_offset = data.len();
+        data.extend_from_slice(&[0, 0, 0, 8]);
+        data.extend_from_slice(b"moov");
+        (bytes::Bytes::from(data), moov_offset)
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_without_network() {
+        let source = failing_source();
+
+        let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+        let options = test_stream_options(RetryPolicy {
+            max_retries: 0,
+            ..Default::default()
+        });
+        AntubeApp::stream_video_data(
+            1,
+            source.clone(),
+            "abc".to_string(),
+            options.clone(),
+            stream_tx,
+        )
+        .await;
+
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ServerConnected { total_size: 16, .. })
+        ));
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ChunkReceived { size: 4, .. })
+        ));
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ChunkReceived { size: 4, .. })
+        ));
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::StreamError { .. })
+        ));
+
+        // Unknown addresses fail before connecting
+        let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+        AntubeApp::stream_video_data(2, source, "missing".to_string(), options, stream_tx).await;
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::StreamError { stream_id: 2, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retry_resumes_from_last_offset() {
+        let source = failing_source();
+
+        let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+        let options = test_stream_options(RetryPolicy {
+            max_retries: 3,
+            initial_backoff: std::time::Duration::from_millis(1),
+        });
+        let task = tokio::spawn(AntubeApp::stream_video_data(
+            1,
+            source,
+            "abc".to_string(),
+            options,
+            stream_tx,
+        ));
+
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ServerConnected { total_size: 16, .. })
+        ));
+        for _ in 0..2 {
+            assert!(matches!(
+                stream_rx.recv().await,
+                Some(StreamEvent::ChunkReceived { size: 4, .. })
+            ));
+        }
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::Retrying {
+                attempt: 1,
+                max_retries: 3,
+                ..
+            })
+        ));
+
+        // The remaining 8 bytes are fetched from where the stream failed
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ChunkReceived { size: 8, .. })
+        ));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_trailing_moov_fetched_first() {
+        let (data, _) = mp4_with_trailing_moov();
+        let mut source = MemorySource::new(container::SNIFF_SIZE);
+        source.insert("abc", data);
+        let source: Arc<dyn DataSource> = Arc::new(source);
+
+        let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+        let options = test_stream_options(RetryPolicy::default());
+        let task = tokio::spawn(AntubeApp::stream_video_data(
+            1,
+            source,
+            "abc".to_string(),
+            options,
+            stream_tx,
+        ));
+
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ServerConnected { .. })
+        ));
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ChunkReceived { size, .. }) if size == container::SNIFF_SIZE
+        ));
+
+        // The 8 byte moov comes next, before the rest of the mdat
+        assert!(matches!(
+            stream_rx.recv().await,
+            Some(StreamEvent::ChunkReceived { size: 8, .. })
+        ));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_trailing_moov_stream_completes() {
+        let (data, moov_offset) = mp4_with_trailing_moov();
+        let mut source = MemorySource::new(container::SNIFF_SIZE);
+        source.insert("abc", data.clone());
+
+        let (stream_tx, mut stream_rx) = mpsc::unbounded_channel();
+        let options = test_stream_options(RetryPolicy::default());
+        let task = tokio::spawn(async move {
+            let video_streamer =
+                VideoStreamer::new(data.len(), options.video_output, &options.pipeline, |_| {})
+                    .unwrap();
+            // The demuxer reads the media data from the start, through the moov fetched first
+            let _ = AntubeApp::serve_seek_requests(
+                1,
+                &source,
+                "abc",
+                &video_streamer,
+                Some(0),
+                Some((moov_offset, data.slice(moov_offset..))),
+                container::SNIFF_SIZE,
+                &options,
+                &stream_tx,
+            )
+            .await;
+        });
+
+        // The head read again isn't counted twice
+        let received = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            let mut received = 0;
+            loop {
+                match stream_rx.recv().await {
+                    Some(StreamEvent::ChunkReceived { size, .. }) => received += size,
+                    Some(StreamEvent::StreamComplete { .. }) => return Some(received),
+                    Some(_) => {}
+                    None => return None,
+                }
+            }
+        })
+        .await;
+        task.abort();
+        assert_eq!(received, Ok(Some(moov_offset - container::SNIFF_SIZE)));
+    }
+}
@@ -0,0 +1,675 @@
+use crate::abr::AbrController;
+use crate::data_source::{
+    sniff, BoxFuture, ChunkStream, DataSource, MemoryRangeReader, RangeChunks, RangeReader,
+    Sniffed, RANGE_CHUNK_SIZE,
+};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::runtime::Handle;
+
+/// Larger data is never checked for a manifest
+const MAX_MANIFEST_SIZE: usize = 1024 * 1024; // 1MB
+
+/// Segments fetched ahead of the one being played
+const PREFETCH_SEGMENTS: usize = 2;
+
+/// A video split into segments stored at their own addresses, played back to back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Format version, also marks the JSON document as an antube manifest
+    pub antube_manifest: u32,
+    /// Segments of a video with a single rendition
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    /// Encodings of the same video at different bitrates, with aligned segment boundaries
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub renditions: Vec<Rendition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rendition {
+    pub name: String,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub address: String,
+    /// Playback duration in seconds
+    pub duration: f64,
+    /// Size in bytes
+    pub size: usize,
+}
+
+impl Rendition {
+    /// Average bitrate in bits per second
+    pub fn bitrate(&self) -> f64 {
+        let size: usize = self.segments.iter().map(|segment| segment.size).sum();
+        let duration: f64 = self.segments.iter().map(|segment| segment.duration).sum();
+        if duration > 0.0 {
+            size as f64 * 8.0 / duration
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Manifest {
+    /// Parse a manifest document, `None` if the data isn't one
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let manifest = serde_json::from_slice::<Manifest>(data).ok()?;
+        let segment_count = manifest.segment_count();
+
+        let valid = if manifest.renditions.is_empty() {
+            segment_count > 0
+        } else {
+            // Renditions can only be switched between if their segments line up
+            segment_count > 0
+                && manifest
+                    .renditions
+                    .iter()
+                    .all(|rendition| rendition.segments.len() == segment_count)
+        };
+        valid.then_some(manifest)
+    }
+
+    /// Renditions lowest bitrate first, a single rendition manifest has one named "default"
+    pub fn renditions(&self) -> Vec<Rendition> {
+        if self.renditions.is_empty() {
+            return vec![Rendition {
+                name: "default".to_string(),
+                segments: self.segments.clone(),
+            }];
+        }
+
+        let mut renditions = self.renditions.clone();
+        renditions.sort_by(|a, b| a.bitrate().total_cmp(&b.bitrate()));
+        renditions
+    }
+
+    pub fn segment_count(&self) -> usize {
+        match self.renditions.first() {
+            Some(rendition) => rendition.segments.len(),
+            None => self.segments.len(),
+        }
+    }
+
+    /// Size of the played data at the lowest bitrate rendition, which playback starts on
+    pub fn total_size(&self) -> usize {
+        self.renditions()[0]
+            .segments
+            .iter()
+            .map(|segment| segment.size)
+            .sum()
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.renditions()[0]
+            .segments
+            .iter()
+            .map(|segment| segment.duration)
+            .sum()
+    }
+}
+
+/// What the data at an address turned out to be
+enum Probe {
+    Manifest(Manifest),
+    /// Any other data, with the reader it was probed through
+    Data(Box<dyn RangeReader>),
+}
+
+/// Open `address` and parse it as a manifest if it is small enough to be one,
+/// small data that isn't one is kept in memory so it isn't fetched twice
+async fn probe(source: &dyn DataSource, address: &str) -> Result<Probe, String> {
+    let data = match sniff(source, address, MAX_MANIFEST_SIZE).await? {
+        Sniffed::Small(data) => data,
+        Sniffed::Large(reader) => return Ok(Probe::Data(reader)),
+    };
+    Ok(match Manifest::parse(&data) {
+        Some(manifest) => Probe::Manifest(manifest),
+        None => Probe::Data(Box::new(MemoryRangeReader::new(data))),
+    })
+}
+
+/// The rendition segments are now fetched from
+#[derive(Debug, Clone)]
+pub struct RenditionChange {
+    pub segment: usize,
+    pub rendition: String,
+    /// Measured download throughput in bytes per second
+    pub throughput: Option<f64>,
+    /// Size of the played data after the switch, with the rest of the segments at this rendition
+    pub data_size: usize,
+}
+
+type RenditionHandler = Arc<dyn Fn(RenditionChange) + Send + Sync>;
+
+/// Renditions picked for the segments of each manifest address, shared between opens
+/// of the address so linear streams and range reads agree on byte offsets
+#[derive(Default)]
+pub struct ManifestLayouts(Mutex<HashMap<String, Arc<Mutex<RenditionSelector>>>>);
+
+impl ManifestLayouts {
+    fn selector(
+        &self,
+        address: &str,
+        manifest: &Manifest,
+        on_rendition: Option<RenditionHandler>,
+    ) -> Arc<Mutex<RenditionSelector>> {
+        self.0
+            .lock()
+            .unwrap()
+            .entry(address.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(RenditionSelector::new(manifest, on_rendition))))
+            .clone()
+    }
+}
+
+/// Serves manifest addresses as the concatenation of their segments, other addresses as is
+pub struct ManifestSource {
+    inner: Arc<dyn DataSource>,
+    on_rendition: Option<RenditionHandler>,
+    layouts: Arc<ManifestLayouts>,
+}
+
+impl ManifestSource {
+    pub fn new(inner: Arc<dyn DataSource>) -> Self {
+        Self {
+            inner,
+            on_rendition: None,
+            layouts: Arc::default(),
+        }
+    }
+
+    /// Report each switch between renditions of a multi-rendition manifest
+    pub fn with_rendition_handler(
+        mut self,
+        handler: impl Fn(RenditionChange) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_rendition = Some(Arc::new(handler));
+        self
+    }
+
+    /// Share the renditions picked with other sources, for readers that open an address repeatedly
+    pub fn with_layouts(mut self, layouts: Arc<ManifestLayouts>) -> Self {
+        self.layouts = layouts;
+        self
+    }
+}
+
+impl DataSource for ManifestSource {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let manifest = match probe(self.inner.as_ref(), address).await? {
+                Probe::Manifest(manifest) => manifest,
+                // Other data streams through the reader it was probed with, small data was read whole
+                Probe::Data(reader) => {
+                    let data_size = reader.data_size();
+                    let chunks = RangeChunks::new(reader, 0, RANGE_CHUNK_SIZE);
+                    return Ok(ChunkStream::new(data_size, chunks));
+                }
+            };
+            println!(
+                "Address {address} is a manifest of {} segments in {} renditions ({:.0}s)",
+                manifest.segment_count(),
+                manifest.renditions().len(),
+                manifest.duration()
+            );
+
+            let selector = self
+                .layouts
+                .selector(address, &manifest, self.on_rendition.clone());
+            let data_size = selector.lock().unwrap().data_size();
+            let segments = fetch_segments(self.inner.clone(), selector);
+            Ok(ChunkStream::new(data_size, segments))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            let manifest = match probe(self.inner.as_ref(), address).await? {
+                Probe::Manifest(manifest) => manifest,
+                Probe::Data(reader) => return Ok(reader),
+            };
+            let selector = self
+                .layouts
+                .selector(address, &manifest, self.on_rendition.clone());
+            let reader = SegmentedRangeReader::new(self.inner.clone(), selector);
+            Ok(Box::new(reader) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+/// Chooses the rendition of each segment and reports switches. A segment's rendition
+/// is final once picked, so the byte offsets of the played data never move.
+struct RenditionSelector {
+    renditions: Vec<Rendition>,
+    abr: AbrController,
+    current: Option<usize>,
+    /// Rendition each segment was picked from
+    picks: Vec<Option<usize>>,
+    on_rendition: Option<RenditionHandler>,
+}
+
+impl RenditionSelector {
+    fn new(manifest: &Manifest, on_rendition: Option<RenditionHandler>) -> Self {
+        let renditions = manifest.renditions();
+        let bitrates = renditions.iter().map(Rendition::bitrate).collect();
+        Self {
+            picks: vec![None; manifest.segment_count()],
+            renditions,
+            abr: AbrController::new(bitrates),
+            current: None,
+            on_rendition,
+        }
+    }
+
+    fn segment_count(&self) -> usize {
+        self.picks.len()
+    }
+
+    /// Rendition of a segment, picked from the measured throughput the first time it is needed.
+    /// Switches only happen at segment boundaries.
+    fn pick(&mut self, segment: usize) -> usize {
+        if let Some(rendition) = self.picks[segment] {
+            return rendition;
+        }
+
+        let rendition = self.abr.select();
+        let switched = self.current != Some(rendition);
+        self.current = Some(rendition);
+        self.picks[segment] = Some(rendition);
+
+        if switched && self.renditions.len() > 1 {
+            println!(
+                "Switching to rendition {} at segment {segment}",
+                self.renditions[rendition].name
+            );
+            if let Some(handler) = &self.on_rendition {
+                handler(RenditionChange {
+                    segment,
+                    rendition: self.renditions[rendition].name.clone(),
+                    throughput: self.abr.throughput(),
+                    data_size: self.data_size(),
+                });
+            }
+        }
+        rendition
+    }
+
+    fn segment(&self, rendition: usize, segment: usize) -> &Segment {
+        &self.renditions[rendition].segments[segment]
+    }
+
+    /// Size of the played data, segments not picked yet counted at the current rendition
+    fn data_size(&self) -> usize {
+        let assumed = self.current.unwrap_or(0);
+        self.picks
+            .iter()
+            .enumerate()
+            .map(|(segment, pick)| self.segment(pick.unwrap_or(assumed), segment).size)
+            .sum()
+    }
+
+    /// Segment holding `position` and the offset into it, picking the segments up to it
+    /// so their sizes are settled
+    fn locate(&mut self, position: usize) -> Option<(usize, usize)> {
+        let mut start = 0;
+        for segment in 0..self.segment_count() {
+            let rendition = self.pick(segment);
+            let size = self.segment(rendition, segment).size;
+            if position < start + size {
+                return Some((segment, position - start));
+            }
+            start += size;
+        }
+        None
+    }
+}
+
+/// Fetch whole segments in order on a background thread, at most `PREFETCH_SEGMENTS` ahead of the reader
+fn fetch_segments(
+    inner: Arc<dyn DataSource>,
+    selector: Arc<Mutex<RenditionSelector>>,
+) -> impl Iterator<Item = Result<Bytes, String>> + Send {
+    let (segment_tx, segment_rx) = std::sync::mpsc::sync_channel(PREFETCH_SEGMENTS);
+    let handle = Handle::current();
+
+    std::thread::spawn(move || {
+        let segment_count = selector.lock().unwrap().segment_count();
+        for index in 0..segment_count {
+            let segment = {
+                let mut selector = selector.lock().unwrap();
+                let rendition = selector.pick(index);
+                selector.segment(rendition, index).clone()
+            };
+
+            let started = Instant::now();
+            let result = handle
+                .block_on(fetch_segment(inner.as_ref(), &segment))
+                .map_err(|e| format!("Failed to fetch segment {index}: {e}"));
+            if let Ok(data) = &result {
+                selector
+                    .lock()
+                    .unwrap()
+                    .abr
+                    .record(data.len(), started.elapsed());
+            }
+            let failed = result.is_err();
+
+            // Stop once the reader is gone or after the first failure
+            if segment_tx.send(result).is_err() || failed {
+                break;
+            }
+        }
+    });
+
+    segment_rx.into_iter()
+}
+
+async fn fetch_segment(source: &dyn DataSource, segment: &Segment) -> Result<Bytes, String> {
+    let stream = source.open(&segment.address).await?;
+    check_segment_size(segment, stream.data_size())?;
+
+    let mut data = Vec::with_capacity(segment.size);
+    for chunk in stream {
+        data.extend_from_slice(&chunk?);
+    }
+    Ok(Bytes::from(data))
+}
+
+fn check_segment_size(segment: &Segment, data_size: usize) -> Result<(), String> {
+    if data_size != segment.size {
+        return Err(format!(
+            "{} is {data_size} bytes, the manifest says {}",
+            segment.address, segment.size
+        ));
+    }
+    Ok(())
+}
+
+/// Where a sequential read continues in the segments
+struct Cursor {
+    position: usize,
+    segment: usize,
+    rendition: usize,
+    offset: usize,
+}
+
+/// Random access over the concatenated segments, opening each segment when first read.
+/// Offsets follow the renditions picked for the segments, the same ones the linear stream plays.
+///
+/// Like every range read, reads block and must run outside async code, segments are opened
+/// by blocking on the runtime the reader was created on.
+struct SegmentedRangeReader {
+    inner: Arc<dyn DataSource>,
+    handle: Handle,
+    selector: Arc<Mutex<RenditionSelector>>,
+    cursor: Option<Cursor>,
+    /// The segment being read, with its rendition
+    reader: Option<(usize, usize, Box<dyn RangeReader>)>,
+}
+
+impl SegmentedRangeReader {
+    fn new(inner: Arc<dyn DataSource>, selector: Arc<Mutex<RenditionSelector>>) -> Self {
+        Self {
+            inner,
+            handle: Handle::current(),
+            selector,
+            cursor: None,
+            reader: None,
+        }
+    }
+
+    /// Start reading at an arbitrary offset, `None` at the end of the data
+    fn seek(&mut self, position: usize) -> Option<Cursor> {
+        let mut selector = self.selector.lock().unwrap();
+        let (segment, offset) = selector.locate(position)?;
+        Some(Cursor {
+            position,
+            segment,
+            rendition: selector.pick(segment),
+            offset,
+        })
+    }
+
+    fn read_segment(
+        &mut self,
+        segment: usize,
+        rendition: usize,
+        offset: usize,
+        len: usize,
+    ) -> Result<Bytes, String> {
+        let is_open = matches!(&self.reader, Some((s, r, _)) if *s == segment && *r == rendition);
+        if !is_open {
+            let info = self
+                .selector
+                .lock()
+                .unwrap()
+                .segment(rendition, segment)
+                .clone();
+            let reader = self
+                .handle
+                .block_on(self.inner.open_ranged(&info.address))?;
+            check_segment_size(&info, reader.data_size())?;
+            self.reader = Some((segment, rendition, reader));
+        }
+
+        let Some((_, _, reader)) = self.reader.as_mut() else {
+            return Err(format!("Segment {segment} is not open"));
+        };
+        let started = Instant::now();
+        let chunk = reader.read_range(offset, len)?;
+        self.selector
+            .lock()
+            .unwrap()
+            .abr
+            .record(chunk.len(), started.elapsed());
+        Ok(chunk)
+    }
+}
+
+impl RangeReader for SegmentedRangeReader {
+    fn data_size(&self) -> usize {
+        self.selector.lock().unwrap().data_size()
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        let data_size = self.data_size();
+        if start > data_size {
+            return Err(format!("Range start {start} is past the end of the data"));
+        }
+
+        let cursor = match self.cursor.take() {
+            Some(cursor) if cursor.position == start => Some(cursor),
+            _ => self.seek(start),
+        };
+        let Some(mut cursor) = cursor else {
+            return Ok(Bytes::new());
+        };
+
+        let segment_count = self.selector.lock().unwrap().segment_count();
+        let mut data = Vec::with_capacity(len.min(data_size - start));
+        while data.len() < len && cursor.segment < segment_count {
+            let segment_size = self
+                .selector
+                .lock()
+                .unwrap()
+                .segment(cursor.rendition, cursor.segment)
+                .size;
+            if cursor.offset >= segment_size {
+                // Renditions are only switched at segment boundaries
+                cursor.segment += 1;
+                cursor.offset = 0;
+                if cursor.segment < segment_count {
+                    cursor.rendition = self.selector.lock().unwrap().pick(cursor.segment);
+                }
+                continue;
+            }
+
+            let chunk = self.read_segment(
+                cursor.segment,
+                cursor.rendition,
+                cursor.offset,
+                len - data.len(),
+            )?;
+            if chunk.is_empty() {
+                cursor.offset = segment_size;
+                continue;
+            }
+            cursor.offset += chunk.len();
+            data.extend_from_slice(&chunk);
+        }
+
+        cursor.position = start + data.len();
+        self.cursor = Some(cursor);
+        Ok(Bytes::from(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::{BlockingRangeReader, MemorySource};
+
+    fn segment(address: &str, size: usize) -> Segment {
+        Segment {
+            address: address.to_string(),
+            duration: 2.0,
+            size,
+        }
+    }
+
+    fn segmented_source() -> ManifestSource {
+        let mut source = MemorySource::new(4);
+        source.insert("seg0", vec![0u8; 6]);
+        source.insert("seg1", vec![1u8; 10]);
+
+        let manifest = Manifest {
+            antube_manifest: 1,
+            segments: vec![segment("seg0", 6), segment("seg1", 10)],
+            renditions: Vec::new(),
+        };
+        source.insert("manifest", serde_json::to_vec(&manifest).unwrap());
+        source.insert("plain", vec![7u8; 5]);
+
+        ManifestSource::new(Arc::new(source))
+    }
+
+    #[tokio::test]
+    async fn test_manifest_concatenates_segments() {
+        let source = segmented_source();
+
+        let stream = source.open("manifest").await.unwrap();
+        assert_eq!(stream.data_size(), 16);
+        let data: Vec<u8> = stream.flat_map(|chunk| chunk.unwrap().to_vec()).collect();
+        assert_eq!(data, [vec![0u8; 6], vec![1u8; 10]].concat());
+
+        // Other addresses are passed through, small ones from the data read while probing
+        let plain: Vec<u8> = source
+            .open("plain")
+            .await
+            .unwrap()
+            .flat_map(|chunk| chunk.unwrap().to_vec())
+            .collect();
+        assert_eq!(plain, [7u8; 5]);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_range_across_segments() {
+        let source = segmented_source();
+
+        let reader = BlockingRangeReader::new(source.open_ranged("manifest").await.unwrap());
+        assert_eq!(reader.data_size(), 16);
+        assert_eq!(&reader.read_range(4, 4).await.unwrap()[..], &[0, 0, 1, 1]);
+        assert_eq!(reader.read_range(14, 10).await.unwrap().len(), 2);
+
+        // Other addresses are read through the reader they were probed with
+        let reader = source.open_ranged("plain").await.unwrap();
+        assert_eq!(reader.data_size(), 5);
+    }
+
+    #[test]
+    fn test_parse_renditions() {
+        let manifest = Manifest::parse(
+            br#"{"antube_manifest": 1, "renditions": [
+                {"name": "720p", "segments": [{"address": "a", "duration": 2.0, "size": 800}]},
+                {"name": "360p", "segments": [{"address": "b", "duration": 2.0, "size": 200}]}
+            ]}"#,
+        )
+        .unwrap();
+
+        let renditions = manifest.renditions();
+        assert_eq!(renditions[0].name, "360p");
+        assert_eq!(renditions[0].bitrate(), 800.0);
+        assert_eq!(manifest.total_size(), 200);
+
+        // Renditions with different segment counts can't be switched between
+        assert!(Manifest::parse(
+            br#"{"antube_manifest": 1, "renditions": [
+                {"name": "720p", "segments": [{"address": "a", "duration": 2.0, "size": 800}]},
+                {"name": "360p", "segments": []}
+            ]}"#
+        )
+        .is_none());
+        assert!(Manifest::parse(b"{\"title\": \"not a manifest\"}").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rendition_switch_reported() {
+        let mut source = MemorySource::new(1024);
+        source.insert("low0", vec![0u8; 10]);
+        source.insert("low1", vec![0u8; 10]);
+        source.insert("high0", vec![1u8; 40]);
+        source.insert("high1", vec![1u8; 40]);
+
+        let manifest = Manifest {
+            antube_manifest: 1,
+            segments: Vec::new(),
+            renditions: vec![
+                Rendition {
+                    name: "high".to_string(),
+                    segments: vec![segment("high0", 40), segment("high1", 40)],
+                },
+                Rendition {
+                    name: "low".to_string(),
+                    segments: vec![segment("low0", 10), segment("low1", 10)],
+                },
+            ],
+        };
+        source.insert("manifest", serde_json::to_vec(&manifest).unwrap());
+
+        let changes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = changes.clone();
+        let source = ManifestSource::new(Arc::new(source))
+            .with_rendition_handler(move |change| recorded.lock().unwrap().push(change));
+
+        // Memory reads are fast enough for the high rendition after the first segment
+        let data: Vec<u8> = source
+            .open("manifest")
+            .await
+            .unwrap()
+            .flat_map(|chunk| chunk.unwrap().to_vec())
+            .collect();
+        assert_eq!(data, [vec![0u8; 10], vec![1u8; 40]].concat());
+
+        let changes = changes.lock().unwrap().clone();
+        let names: Vec<_> = changes.iter().map(|c| c.rendition.as_str()).collect();
+        assert_eq!(names, ["low", "high"]);
+        assert_eq!(changes[1].segment, 1);
+        assert_eq!(changes[1].data_size, 50);
+
+        // Range reads follow the renditions the linear stream played
+        let reader = BlockingRangeReader::new(source.open_ranged("manifest").await.unwrap());
+        assert_eq!(reader.data_size(), 50);
+        assert_eq!(&reader.read_range(8, 4).await.unwrap()[..], &[0, 0, 1, 1]);
+    }
+}
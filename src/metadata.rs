@@ -0,0 +1,102 @@
+use crate::container;
+use crate::data_source::{read_small, DataSource};
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// Larger data is never checked for a metadata document
+const MAX_METADATA_SIZE: usize = 64 * 1024; // 64KB
+
+/// Thumbnails larger than this are not shown
+const MAX_THUMBNAIL_SIZE: usize = 4 * 1024 * 1024; // 4MB
+
+/// Describes a video stored at another address, shown on its stream card
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VideoMetadata {
+    /// Format version, also marks the JSON document as antube metadata
+    pub antube_metadata: u32,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// Playback duration in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// Address of an image shown before playback starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+    /// Address of the video itself, plain data or a manifest
+    pub content: String,
+}
+
+impl VideoMetadata {
+    /// Parse a metadata document, `None` if the data isn't one
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice::<VideoMetadata>(data)
+            .ok()
+            .filter(|metadata| !metadata.content.is_empty())
+    }
+
+    /// Describe uploaded video data, titled after its file name
+    pub fn for_upload(title: &str, data: &[u8], content: String) -> Self {
+        let head = &data[..data.len().min(container::SNIFF_SIZE)];
+        Self {
+            antube_metadata: 1,
+            title: title.to_string(),
+            description: String::new(),
+            duration: container::duration_seconds(head),
+            mime_type: container::sniff(head).map(|c| c.mime_type().to_string()),
+            thumbnail: None,
+            content,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from(serde_json::to_vec_pretty(self).expect("Metadata is always serializable"))
+    }
+}
+
+/// Fetch the data at `address` and parse it as metadata if it is small enough to be a document
+pub async fn resolve(
+    source: &dyn DataSource,
+    address: &str,
+) -> Result<Option<VideoMetadata>, String> {
+    let data = read_small(source, address, MAX_METADATA_SIZE).await?;
+    Ok(data.and_then(|data| VideoMetadata::parse(&data)))
+}
+
+/// Fetch the encoded thumbnail image at `address`
+pub async fn fetch_thumbnail(source: &dyn DataSource, address: &str) -> Result<Bytes, String> {
+    read_small(source, address, MAX_THUMBNAIL_SIZE)
+        .await?
+        .ok_or_else(|| format!("Thumbnail {address} is larger than {MAX_THUMBNAIL_SIZE} bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_source::MemorySource;
+
+    #[tokio::test]
+    async fn test_resolve_metadata() {
+        let mut source = MemorySource::new(1024);
+        source.insert(
+            "metadata",
+            br#"{"antube_metadata": 1, "title": "Cats", "duration": 12.5, "content": "video"}"#
+                .to_vec(),
+        );
+        source.insert("video", vec![0u8; 100]);
+
+        let metadata = resolve(&source, "metadata").await.unwrap().unwrap();
+        assert_eq!(metadata.title, "Cats");
+        assert_eq!(metadata.duration, Some(12.5));
+        assert_eq!(metadata.content, "video");
+        assert_eq!(metadata.thumbnail, None);
+
+        // Round trips through the uploaded document
+        assert_eq!(VideoMetadata::parse(&metadata.to_bytes()), Some(metadata));
+
+        assert_eq!(resolve(&source, "video").await.unwrap(), None);
+    }
+}
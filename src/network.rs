@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+
+/// Networks the autonomi client knows without a definition
+pub const BUILTIN_NETWORKS: [&str; 3] = ["local", "autonomi", "alpha"];
+pub const DEFAULT_NETWORK: &str = "autonomi";
+
+/// Seconds a custom network gets to connect when its definition doesn't say
+const DEFAULT_CONNECT_TIMEOUT_SECS: u64 = 60;
+
+/// A named network defined in the config, such as a private testnet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkDefinition {
+    pub name: String,
+    /// Multiaddrs of the peers the client bootstraps from
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// URLs of peer lists to bootstrap from
+    #[serde(default)]
+    pub contacts_urls: Vec<String>,
+    /// Discover peers on the local network instead of bootstrapping
+    #[serde(default)]
+    pub local: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network_id: Option<u8>,
+    /// Blockchain uploads are paid on
+    #[serde(default)]
+    pub evm: EvmSettings,
+    #[serde(default = "default_connect_timeout_secs")]
+    pub connect_timeout_secs: u64,
+}
+
+fn default_connect_timeout_secs() -> u64 {
+    DEFAULT_CONNECT_TIMEOUT_SECS
+}
+
+/// EVM network of a custom network
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum EvmSettings {
+    #[default]
+    ArbitrumOne,
+    ArbitrumSepolia,
+    Custom {
+        rpc_url: String,
+        payment_token_address: String,
+        data_payments_address: String,
+    },
+}
+
+/// A network to connect to, built in or defined in the config
+#[derive(Debug, Clone, PartialEq)]
+pub enum Network {
+    Local,
+    Autonomi,
+    Alpha,
+    Custom(NetworkDefinition),
+}
+
+impl Network {
+    /// Look a network up by name among the built-in ones and `custom`
+    pub fn resolve(name: &str, custom: &[NetworkDefinition]) -> Result<Self, String> {
+        match name {
+            "local" => Ok(Network::Local),
+            "autonomi" => Ok(Network::Autonomi),
+            "alpha" => Ok(Network::Alpha),
+            _ => custom
+                .iter()
+                .find(|definition| definition.name == name)
+                .map(|definition| Network::Custom(definition.clone()))
+                .ok_or_else(|| {
+                    format!(
+                        "Unknown network {name:?}, expected one of {}",
+                        names(custom).join(", ")
+                    )
+                }),
+        }
+    }
+}
+
+/// Names of the built-in networks followed by the custom ones
+pub fn names(custom: &[NetworkDefinition]) -> Vec<String> {
+    BUILTIN_NETWORKS
+        .iter()
+        .map(|name| name.to_string())
+        .chain(custom.iter().map(|definition| definition.name.clone()))
+        .collect()
+}
+
+/// Check the custom network definitions, listing every problem found
+pub fn validate(custom: &[NetworkDefinition]) -> Result<(), String> {
+    let mut errors = Vec::new();
+    for (i, definition) in custom.iter().enumerate() {
+        let name = &definition.name;
+        if name.trim().is_empty() {
+            errors.push("network names can't be empty".to_string());
+        } else if BUILTIN_NETWORKS.contains(&name.as_str()) {
+            errors.push(format!(
+                "network {name:?} is built in and can't be redefined"
+            ));
+        } else if custom[..i].iter().any(|other| &other.name == name) {
+            errors.push(format!("network {name:?} is defined twice"));
+        }
+
+        if !definition.local && definition.peers.is_empty() && definition.contacts_urls.is_empty() {
+            errors.push(format!(
+                "network {name:?} needs peers or contacts_urls to bootstrap from"
+            ));
+        }
+        if let Some(peer) = definition.peers.iter().find(|peer| !peer.starts_with('/')) {
+            errors.push(format!("network {name:?} peer {peer:?} is not a multiaddr"));
+        }
+        if definition.connect_timeout_secs == 0 {
+            errors.push(format!(
+                "network {name:?} connect_timeout_secs must be above 0"
+            ));
+        }
+
+        if let EvmSettings::Custom {
+            rpc_url,
+            payment_token_address,
+            data_payments_address,
+        } = &definition.evm
+        {
+            if !rpc_url.starts_with("http://") && !rpc_url.starts_with("https://") {
+                errors.push(format!(
+                    "network {name:?} rpc_url {rpc_url:?} is not an HTTP URL"
+                ));
+            }
+            for address in [payment_token_address, data_payments_address] {
+                if !is_evm_address(address) {
+                    errors.push(format!(
+                        "network {name:?} contract address {address:?} is not a 0x hex address"
+                    ));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("; "))
+    }
+}
+
+fn is_evm_address(address: &str) -> bool {
+    address
+        .strip_prefix("0x")
+        .is_some_and(|hex| hex.len() == 40 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_custom_networks() {
+        let custom: Vec<NetworkDefinition> = serde_json::from_str(
+            r#"[{
+                "name": "testnet",
+                "peers": ["/ip4/10.0.0.1/udp/12000/quic-v1/p2p/12D3KooWExample"],
+                "evm": {"custom": {
+                    "rpc_url": "http://10.0.0.1:8545",
+                    "payment_token_address": "0x5FbDB2315678afecb367f032d93F642f64180aa3",
+                    "data_payments_address": "0x8464135c8F25Da09e49BC8782676a84730C318bC"
+                }}
+            }]"#,
+        )
+        .unwrap();
+        assert!(validate(&custom).is_ok());
+        assert_eq!(custom[0].connect_timeout_secs, DEFAULT_CONNECT_TIMEOUT_SECS);
+
+        assert_eq!(Network::resolve("alpha", &custom), Ok(Network::Alpha));
+        assert_eq!(
+            Network::resolve("testnet", &custom),
+            Ok(Network::Custom(custom[0].clone()))
+        );
+        let error = Network::resolve("moon", &custom).unwrap_err();
+        assert!(error.contains("moon") && error.contains("testnet"));
+
+        // Built-in names can't be shadowed and custom networks need somewhere to bootstrap from
+        let invalid = NetworkDefinition {
+            name: "local".to_string(),
+            peers: Vec::new(),
+            ..custom[0].clone()
+        };
+        let error = validate(&[invalid]).unwrap_err();
+        assert!(error.contains("built in") && error.contains("bootstrap"));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::data_map;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Videos to play in order, saved to a local file or published as public data
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Playlist {
+    /// Format version, also marks the JSON document as an antube playlist
+    pub antube_playlist: u32,
+    pub name: String,
+    pub items: Vec<PlaylistItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistItem {
+    pub address: String,
+    /// Network the item plays from, set when queued since saved playlists are network independent
+    #[serde(skip)]
+    pub network: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+impl Playlist {
+    pub fn new(name: &str, items: Vec<PlaylistItem>) -> Self {
+        Self {
+            antube_playlist: 1,
+            name: name.to_string(),
+            items,
+        }
+    }
+
+    /// Parse a playlist document, `None` if the data isn't one
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        serde_json::from_slice(data).ok()
+    }
+
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from(serde_json::to_vec_pretty(self).expect("Playlist is always serializable"))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data =
+            std::fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        Self::parse(&data).ok_or_else(|| format!("{} is not a playlist", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_bytes())
+            .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+    }
+}
+
+/// What plays after the last item of the queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Repeat {
+    #[default]
+    Off,
+    All,
+    One,
+}
+
+impl Repeat {
+    pub fn cycle(self) -> Self {
+        match self {
+            Repeat::Off => Repeat::All,
+            Repeat::All => Repeat::One,
+            Repeat::One => Repeat::Off,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Repeat::Off => "Repeat: off",
+            Repeat::All => "Repeat: all",
+            Repeat::One => "Repeat: one",
+        }
+    }
+}
+
+/// Items queued for playback and the order they play in
+#[derive(Debug, Default)]
+pub struct Queue {
+    items: Vec<PlaylistItem>,
+    /// Item indexes in play order, shuffled or not
+    order: Vec<usize>,
+    /// Position in `order` of the item playing
+    position: Option<usize>,
+    shuffle: bool,
+    pub repeat: Repeat,
+}
+
+impl Queue {
+    pub fn items(&self) -> &[PlaylistItem] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn is_shuffled(&self) -> bool {
+        self.shuffle
+    }
+
+    /// Item indexes in the order they play
+    pub fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Append an item and return its index
+    pub fn add(&mut self, item: PlaylistItem) -> usize {
+        self.items.push(item);
+        let index = self.items.len() - 1;
+        self.order.push(index);
+        index
+    }
+
+    pub fn set_title(&mut self, index: usize, title: &str) {
+        if let Some(item) = self.items.get_mut(index) {
+            item.title.get_or_insert_with(|| title.to_string());
+        }
+    }
+
+    /// Remove an item, the one after it plays next if it was the current one
+    pub fn remove(&mut self, index: usize) {
+        if index >= self.items.len() {
+            return;
+        }
+        self.items.remove(index);
+
+        let removed_at = self.order.iter().position(|&i| i == index);
+        self.order.retain(|&i| i != index);
+        for i in &mut self.order {
+            if *i > index {
+                *i -= 1;
+            }
+        }
+
+        if let (Some(position), Some(removed_at)) = (self.position, removed_at) {
+            if removed_at <= position {
+                self.position = position.checked_sub(1);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self {
+            repeat: self.repeat,
+            shuffle: self.shuffle,
+            ..Self::default()
+        };
+    }
+
+    /// Index of the item playing
+    pub fn current(&self) -> Option<usize> {
+        self.position.map(|position| self.order[position])
+    }
+
+    /// Make an item the one playing
+    pub fn play(&mut self, index: usize) {
+        self.position = self.order.iter().position(|&i| i == index);
+    }
+
+    /// Index of the item that plays after the current one
+    pub fn peek_next(&self) -> Option<usize> {
+        let position = match self.position {
+            None => 0,
+            Some(position) if self.repeat == Repeat::One => position,
+            Some(position) if position + 1 < self.order.len() => position + 1,
+            Some(_) if self.repeat == Repeat::All => 0,
+            Some(_) => return None,
+        };
+        self.order.get(position).copied()
+    }
+
+    /// Move on to the next item, `None` once the queue is done
+    pub fn advance(&mut self) -> Option<usize> {
+        let next = self.peek_next();
+        match next {
+            Some(index) => self.play(index),
+            None => self.position = None,
+        }
+        next
+    }
+
+    /// Shuffle the items after the current one, or go back to the order they were added in
+    pub fn set_shuffle(&mut self, shuffle: bool, seed: u64) {
+        self.shuffle = shuffle;
+        let current = self.current();
+
+        self.order = (0..self.items.len()).collect();
+        if shuffle {
+            // The current item stays first so playback carries on where it is
+            if let Some(current) = current {
+                self.order.swap(0, current);
+            }
+            let start = usize::from(current.is_some());
+            shuffle_in_place(&mut self.order[start..], seed);
+        }
+        if let Some(current) = current {
+            self.play(current);
+        }
+    }
+
+    /// Playlist of the queued items without private stand-ins, which only work this session,
+    /// and how many were left out
+    pub fn to_playlist(&self, name: &str) -> (Playlist, usize) {
+        let items: Vec<PlaylistItem> = self
+            .items
+            .iter()
+            .filter(|item| !data_map::is_private(&item.address))
+            .cloned()
+            .collect();
+        let skipped = self.items.len() - items.len();
+        (Playlist::new(name, items), skipped)
+    }
+}
+
+/// Fisher-Yates shuffle driven by a xorshift generator
+fn shuffle_in_place(items: &mut [usize], seed: u64) {
+    let mut state = seed | 1;
+    for i in (1..items.len()).rev() {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        items.swap(i, (state % (i as u64 + 1)) as usize);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_of(count: usize) -> Queue {
+        let mut queue = Queue::default();
+        for i in 0..count {
+            queue.add(PlaylistItem {
+                address: format!("video{i}"),
+                network: None,
+                title: None,
+            });
+        }
+        queue
+    }
+
+    #[test]
+    fn test_queue_advance_and_repeat() {
+        let mut queue = queue_of(3);
+        assert_eq!(queue.advance(), Some(0));
+        assert_eq!(queue.advance(), Some(1));
+        assert_eq!(queue.advance(), Some(2));
+        assert_eq!(queue.peek_next(), None);
+
+        queue.repeat = Repeat::All;
+        assert_eq!(queue.peek_next(), Some(0));
+        queue.repeat = Repeat::One;
+        assert_eq!(queue.advance(), Some(2));
+
+        // Removing the current item plays the one after it next
+        queue.repeat = Repeat::Off;
+        queue.play(1);
+        queue.remove(1);
+        assert_eq!(queue.items().len(), 2);
+        assert_eq!(queue.advance(), Some(1));
+        assert_eq!(queue.items()[1].address, "video2");
+
+        // Private items aren't saved or published
+        queue.add(PlaylistItem {
+            address: "private:0123456789abcdef".to_string(),
+            network: None,
+            title: None,
+        });
+        let (playlist, skipped) = queue.to_playlist("test");
+        assert_eq!((playlist.items.len(), skipped), (2, 1));
+    }
+
+    #[test]
+    fn test_shuffle_keeps_current_first() {
+        let mut queue = queue_of(10);
+        queue.play(4);
+        queue.set_shuffle(true, 42);
+        assert_eq!(queue.current(), Some(4));
+        assert_eq!(queue.order()[0], 4);
+
+        let mut order = queue.order().to_vec();
+        order.sort();
+        assert_eq!(order, (0..10).collect::<Vec<_>>());
+
+        queue.set_shuffle(false, 0);
+        assert_eq!(queue.order(), (0..10).collect::<Vec<_>>());
+        assert_eq!(queue.peek_next(), Some(5));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::data_source::{BoxFuture, DataSource, SourceProvider};
+use crate::manifest::{ManifestLayouts, ManifestSource};
+use crate::network::{Network, NetworkDefinition};
+use crate::server::Server;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// How often connected clients are checked
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A health check taking longer than this counts as failed
+const HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// State of a network's shared client, shown in the header
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+/// A client shared by the pool, checked periodically while connected
+pub trait PooledClient: Clone + Send + Sync + 'static {
+    fn health_check(&self) -> BoxFuture<'_, Result<(), String>>;
+}
+
+impl PooledClient for Server {
+    fn health_check(&self) -> BoxFuture<'_, Result<(), String>> {
+        Box::pin(Server::health_check(self))
+    }
+}
+
+/// Connects the client of a network by name, given the custom network definitions
+type Connector<C> = Arc<
+    dyn Fn(String, Vec<NetworkDefinition>) -> BoxFuture<'static, Result<C, String>> + Send + Sync,
+>;
+
+/// A connected client, numbered in the order clients connected
+#[derive(Clone)]
+struct Pooled<C> {
+    client: C,
+    id: u64,
+}
+
+/// Client of a network, `None` until connected, locked while connecting
+type Slot<C> = Arc<tokio::sync::Mutex<Option<Pooled<C>>>>;
+
+struct PoolState<C> {
+    networks: Vec<NetworkDefinition>,
+    slots: HashMap<String, Slot<C>>,
+    statuses: HashMap<String, ConnectionStatus>,
+    /// Renditions picked for the manifests of each network, by network name
+    layouts: HashMap<String, Arc<ManifestLayouts>>,
+    /// Data source of each network's client with the client's number, replaced when the
+    /// client is, so readers opened through an older one can tell
+    sources: HashMap<String, (u64, Arc<dyn DataSource>)>,
+    /// Clients connected so far
+    connections: u64,
+}
+
+/// One client per network shared by all streams, connected on first use and reconnected when it fails
+#[derive(Clone)]
+pub struct ServerPool<C = Server> {
+    state: Arc<Mutex<PoolState<C>>>,
+    connect: Connector<C>,
+}
+
+impl ServerPool {
+    pub fn new(networks: Vec<NetworkDefinition>) -> Self {
+        Self::with_connector(
+            networks,
+            Arc::new(|environment, networks| {
+                Box::pin(async move { Server::new(&environment, &networks).await })
+            }),
+        )
+    }
+
+    /// Data sources of each network's shared client with manifests resolved, for the gateway and `autonomisrc`
+    pub fn source_provider(&self) -> SourceProvider {
+        let pool = self.clone();
+        Arc::new(move |network| {
+            let pool = pool.clone();
+            Box::pin(async move {
+                if !pool.is_defined(&network) {
+                    return Ok(None);
+                }
+                let server = pool.connection(&network).await?;
+                Ok(Some(pool.source(&network, server)))
+            })
+        })
+    }
+
+    /// Data source of `environment` reading through `server`, the same one until the client or network is replaced
+    fn source(&self, environment: &str, server: Pooled<Server>) -> Arc<dyn DataSource> {
+        let layouts = self.layouts(environment);
+        let mut state = self.state.lock().unwrap();
+        let cached = state.sources.get(environment);
+        if let Some((_, source)) = cached.filter(|(id, _)| *id == server.id) {
+            return source.clone();
+        }
+
+        // A client replaced since it was handed out doesn't take its replacement's place
+        let replace = cached.is_none_or(|(id, _)| *id < server.id);
+        let source = ManifestSource::new(Arc::new(server.client)).with_layouts(layouts);
+        let source = Arc::new(source) as Arc<dyn DataSource>;
+        if replace {
+            state
+                .sources
+                .insert(environment.to_string(), (server.id, source.clone()));
+        }
+        source
+    }
+}
+
+impl<C: PooledClient> ServerPool<C> {
+    fn with_connector(networks: Vec<NetworkDefinition>, connect: Connector<C>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(PoolState {
+                networks,
+                slots: HashMap::new(),
+                statuses: HashMap::new(),
+                layouts: HashMap::new(),
+                sources: HashMap::new(),
+                connections: 0,
+            })),
+            connect,
+        }
+    }
+
+    /// Replace the custom network definitions, dropping the clients of those that changed
+    pub fn set_networks(&self, networks: Vec<NetworkDefinition>) {
+        let mut state = self.state.lock().unwrap();
+        let changed: Vec<String> = state
+            .networks
+            .iter()
+            .filter(|definition| !networks.contains(definition))
+            .map(|definition| definition.name.clone())
+            .collect();
+        for name in changed {
+            state.slots.remove(&name);
+            state.statuses.remove(&name);
+            state.layouts.remove(&name);
+            state.sources.remove(&name);
+        }
+        state.networks = networks;
+    }
+
+    /// Whether `environment` is a built-in network or one of the custom definitions
+    fn is_defined(&self, environment: &str) -> bool {
+        Network::resolve(environment, &self.state.lock().unwrap().networks).is_ok()
+    }
+
+    /// Every open of a manifest on `environment` reads the same renditions, so byte offsets agree
+    /// between requests
+    fn layouts(&self, environment: &str) -> Arc<ManifestLayouts> {
+        self.state
+            .lock()
+            .unwrap()
+            .layouts
+            .entry(environment.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Status of every network used so far, by name
+    pub fn statuses(&self) -> Vec<(String, ConnectionStatus)> {
+        let state = self.state.lock().unwrap();
+        let mut statuses: Vec<_> = state
+            .statuses
+            .iter()
+            .map(|(name, status)| (name.clone(), status.clone()))
+            .collect();
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+        statuses
+    }
+
+    /// The shared client of `environment`, connecting it first if needed
+    pub async fn get(&self, environment: &str) -> Result<C, String> {
+        self.connection(environment)
+            .await
+            .map(|pooled| pooled.client)
+    }
+
+    async fn connection(&self, environment: &str) -> Result<Pooled<C>, String> {
+        let (slot, networks) = {
+            let mut state = self.state.lock().unwrap();
+            // Unknown names get no slot or status, anyone can ask for any name through the gateway
+            Network::resolve(environment, &state.networks)?;
+            let slot = state
+                .slots
+                .entry(environment.to_string())
+                .or_default()
+                .clone();
+            (slot, state.networks.clone())
+        };
+
+        // Streams started while connecting wait for the same client instead of bootstrapping their own
+        let mut server = slot.lock().await;
+        if let Some(server) = server.as_ref() {
+            return Ok(server.clone());
+        }
+
+        self.set_status(environment, ConnectionStatus::Connecting);
+        match (self.connect)(environment.to_string(), networks).await {
+            Ok(client) => {
+                self.set_status(environment, ConnectionStatus::Connected);
+                let id = {
+                    let mut state = self.state.lock().unwrap();
+                    state.connections += 1;
+                    state.connections
+                };
+                let connected = Pooled { client, id };
+                *server = Some(connected.clone());
+                Ok(connected)
+            }
+            Err(e) => {
+                self.set_status(environment, ConnectionStatus::Failed(e.clone()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Start connecting to `environment` in the background, so the first stream doesn't wait for it
+    pub fn connect_eagerly(&self, environment: &str) {
+        let pool = self.clone();
+        let environment = environment.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = pool.get(&environment).await {
+                println!("Failed to connect to {environment} at startup: {e}");
+            }
+        });
+    }
+
+    /// Check the connected clients periodically, reconnecting those that fail
+    pub fn spawn_health_checks(&self) {
+        let pool = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+            // The first tick completes immediately, before anything connected
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                pool.check_health().await;
+            }
+        });
+    }
+
+    async fn check_health(&self) {
+        let slots: Vec<(String, Slot<C>)> = {
+            let state = self.state.lock().unwrap();
+            state
+                .slots
+                .iter()
+                .map(|(name, slot)| (name.clone(), slot.clone()))
+                .collect()
+        };
+
+        for (environment, slot) in slots {
+            let Some(Pooled { client: server, .. }) = slot.lock().await.clone() else {
+                continue;
+            };
+            let result = tokio::time::timeout(HEALTH_CHECK_TIMEOUT, server.health_check())
+                .await
+                .unwrap_or_else(|_| Err("Health check timed out".to_string()));
+
+            if let Err(e) = result {
+                println!("Client for {environment} is unhealthy, reconnecting: {e}");
+                *slot.lock().await = None;
+                self.set_status(&environment, ConnectionStatus::Failed(e));
+                if let Err(e) = self.get(&environment).await {
+                    println!("Failed to reconnect to {environment}: {e}");
+                }
+            }
+        }
+    }
+
+    fn set_status(&self, environment: &str, status: ConnectionStatus) {
+        self.state
+            .lock()
+            .unwrap()
+            .statuses
+            .insert(environment.to_string(), status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    #[derive(Clone)]
+    struct MockClient {
+        healthy: Arc<AtomicBool>,
+    }
+
+    impl PooledClient for MockClient {
+        fn health_check(&self) -> BoxFuture<'_, Result<(), String>> {
+            let healthy = self.healthy.load(Ordering::Relaxed);
+            Box::pin(async move { healthy.then_some(()).ok_or("Unreachable".to_string()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pool_coalesces_and_reconnects() {
+        let connects = Arc::new(AtomicUsize::new(0));
+        let healthy = Arc::new(AtomicBool::new(true));
+        let (counter, flag) = (connects.clone(), healthy.clone());
+        let pool = ServerPool::with_connector(
+            Vec::new(),
+            Arc::new(move |_, _| {
+                counter.fetch_add(1, Ordering::Relaxed);
+                let healthy = flag.clone();
+                Box::pin(async move {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Ok(MockClient { healthy })
+                })
+            }),
+        );
+
+        // Streams started while connecting share the same connection
+        let (first, second) = tokio::join!(pool.get("local"), pool.get("local"));
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(connects.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            pool.statuses(),
+            vec![("local".to_string(), ConnectionStatus::Connected)]
+        );
+
+        // Healthy clients are kept
+        pool.check_health().await;
+        assert_eq!(connects.load(Ordering::Relaxed), 1);
+
+        // Failing clients are replaced
+        healthy.store(false, Ordering::Relaxed);
+        pool.check_health().await;
+        assert_eq!(connects.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            pool.statuses(),
+            vec![("local".to_string(), ConnectionStatus::Connected)]
+        );
+
+        // Manifest layouts are kept per network, and dropped with a network's definition
+        let lab: NetworkDefinition = serde_json::from_str(r#"{"name": "lab"}"#).unwrap();
+        pool.set_networks(vec![lab]);
+        let layouts = pool.layouts("lab");
+        assert!(Arc::ptr_eq(&layouts, &pool.layouts("lab")));
+        assert!(!Arc::ptr_eq(&layouts, &pool.layouts("local")));
+        pool.set_networks(Vec::new());
+        assert!(!Arc::ptr_eq(&layouts, &pool.layouts("lab")));
+
+        // Unknown networks are rejected without leaving a status behind
+        assert!(pool.get("moon").await.is_err());
+        assert!(pool.statuses().iter().all(|(name, _)| name != "moon"));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::container;
+
+/// Seconds of playback collected before the pipeline starts
+pub const DEFAULT_PREBUFFER_SECONDS: f64 = 8.0;
+
+/// Prebuffer used when the bitrate can't be worked out from the header
+pub const FALLBACK_PREBUFFER_SIZE: usize = 10 * 1024 * 1024; // 10MB
+
+const MIN_PREBUFFER_SIZE: usize = 512 * 1024; // 512KB
+const MAX_PREBUFFER_SIZE: usize = 64 * 1024 * 1024; // 64MB
+
+/// How much data is collected before the pipeline starts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrebufferTarget {
+    pub bytes: usize,
+    /// Average bitrate in bytes per second, when the duration is known
+    pub bitrate: Option<f64>,
+    pub seconds: Option<f64>,
+}
+
+impl PrebufferTarget {
+    /// Work out the target from the first bytes of the data, falling back to a fixed size
+    pub fn from_header(head: &[u8], data_size: usize, seconds: f64) -> Self {
+        let bitrate = container::duration_seconds(head).map(|duration| data_size as f64 / duration);
+
+        match bitrate {
+            Some(bitrate) => Self {
+                bytes: ((bitrate * seconds) as usize).clamp(MIN_PREBUFFER_SIZE, MAX_PREBUFFER_SIZE),
+                bitrate: Some(bitrate),
+                seconds: Some(seconds),
+            },
+            None => Self {
+                bytes: FALLBACK_PREBUFFER_SIZE,
+                bitrate: None,
+                seconds: None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prebuffer_target() {
+        // 100s of MP4 at 1MB/s
+        let mut head = vec![0, 0, 0, 16];
+        head.extend_from_slice(b"ftypisom\0\0\0\0");
+        let mut mvhd = vec![0u8; 20];
+        mvhd[12..16].copy_from_slice(&1u32.to_be_bytes());
+        mvhd[16..20].copy_from_slice(&100u32.to_be_bytes());
+        head.extend_from_slice(&(16 + mvhd.len() as u32).to_be_bytes());
+        head.extend_from_slice(b"moov");
+        head.extend_from_slice(&(8 + mvhd.len() as u32).to_be_bytes());
+        head.extend_from_slice(b"mvhd");
+        head.extend_from_slice(&mvhd);
+
+        let target = PrebufferTarget::from_header(&head, 100 * 1024 * 1024, 4.0);
+        assert_eq!(target.bytes, 4 * 1024 * 1024);
+        assert_eq!(target.seconds, Some(4.0));
+
+        let fallback = PrebufferTarget::from_header(b"unknown", 100 * 1024 * 1024, 4.0);
+        assert_eq!(fallback.bytes, FALLBACK_PREBUFFER_SIZE);
+        assert_eq!(fallback.bitrate, None);
+    }
+}
@@ -1,8 +1,27 @@
-use autonomi::data::DataAddress;
-use autonomi::Client;
+use crate::channel::{FeedVideo, CHANNEL_REGISTER_NAME};
+use crate::data_map;
+use crate::data_source::{BoxFuture, ChunkStream, DataSource, RangeReader};
+use crate::metadata::{self, VideoMetadata};
+use crate::network::{EvmSettings, Network, NetworkDefinition};
+use autonomi::client::payment::PaymentOption;
+use autonomi::client::register::{RegisterAddress, RegisterError};
+use autonomi::data::private::DataMapChunk;
+use autonomi::data::{DataAddress, DataStream};
+use autonomi::{
+    AttoTokens, Client, ClientConfig, InitialPeersConfig, Multiaddr, PointerAddress, SecretKey,
+    Wallet, XorName,
+};
+use bytes::Bytes;
+use std::io::Write;
+use std::time::Duration;
 
-pub const ENVIRONMENTS: [&str; 3] = ["local", "autonomi", "alpha"];
-pub const DEFAULT_ENVIRONMENT: &str = "autonomi";
+/// Maximum size of a self-encrypted chunk
+const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+/// Self-encryption always splits data into at least 3 chunks
+const MIN_CHUNK_COUNT: usize = 3;
+
+/// Environment variable holding the hex encoded wallet private key used for uploads
+pub const SECRET_KEY_ENV: &str = "SECRET_KEY";
 
 #[derive(Clone)]
 pub struct Server {
@@ -10,18 +29,34 @@ pub struct Server {
 }
 
 impl Server {
-    pub async fn new(environment: &str) -> Result<Self, String> {
+    /// Connect to the network named `environment`, built in or one of the `custom` ones
+    pub async fn new(environment: &str, custom: &[NetworkDefinition]) -> Result<Self, String> {
         println!("Initializing client with environment: {environment:?}");
 
-        let client = init_client(environment).await?;
+        let network = Network::resolve(environment, custom)?;
+        let client = init_client(&network).await?;
         println!("Client initialized for streaming");
 
         Ok(Self { client })
     }
 
-    pub async fn stream_data(&self, address: &str) -> Result<autonomi::data::DataStream, String> {
+    pub async fn stream_data(&self, address: &str) -> Result<DataStream, String> {
         println!("Starting to stream data from address: {address}");
 
+        // Private data streams under a stand-in address, its data map is never logged
+        if data_map::is_private(address) {
+            let data_map = data_map::lookup(address).ok_or_else(|| {
+                format!("{address} wasn't opened this session, enter its data map again")
+            })?;
+            let data_map = DataMapChunk::from_hex(&data_map)
+                .map_err(|_| "Invalid private data map".to_string())?;
+            return self
+                .client
+                .data_stream(&data_map)
+                .await
+                .map_err(|e| format!("Failed to start streaming private data: {e}"));
+        }
+
         // Parse the address
         let data_address =
             DataAddress::from_hex(address).map_err(|e| format!("Invalid address format: {e}"))?;
@@ -35,16 +70,291 @@ impl Server {
 
         Ok(stream)
     }
+
+    /// Fetch and parse the video metadata at `address`, `None` if the data isn't metadata
+    pub async fn fetch_metadata(&self, address: &str) -> Result<Option<VideoMetadata>, String> {
+        metadata::resolve(self, address).await
+    }
+
+    pub async fn estimate_upload_cost(&self, data: Bytes) -> Result<AttoTokens, String> {
+        println!("Estimating upload cost for {} bytes", data.len());
+
+        self.client
+            .data_cost(data)
+            .await
+            .map_err(|e| format!("Failed to estimate upload cost: {e}"))
+    }
+
+    /// Look up a pointer that doesn't exist, which only succeeds if the client still reaches the network
+    pub async fn health_check(&self) -> Result<(), String> {
+        let probe = PointerAddress::new(SecretKey::random().public_key());
+        self.client
+            .pointer_check_existence(&probe)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Health check failed: {e}"))
+    }
+
+    pub async fn upload_public(
+        &self,
+        data: Bytes,
+        wallet: &Wallet,
+    ) -> Result<(AttoTokens, DataAddress), String> {
+        println!("Uploading {} bytes as public data", data.len());
+
+        let (cost, address) = self
+            .client
+            .data_put_public(data, PaymentOption::from(wallet))
+            .await
+            .map_err(|e| format!("Failed to upload data: {e}"))?;
+
+        println!(
+            "Upload complete at address {}, cost: {cost}",
+            address.to_hex()
+        );
+        Ok((cost, address))
+    }
+
+    /// Video addresses published to the channel at `address`, oldest first
+    pub async fn channel_videos(&self, address: &str) -> Result<Vec<String>, String> {
+        let register = RegisterAddress::from_hex(address)
+            .map_err(|e| format!("Invalid channel address: {e}"))?;
+
+        let values = self
+            .client
+            .register_history(&register)
+            .collect()
+            .await
+            .map_err(|e| format!("Failed to read channel {address}: {e}"))?;
+
+        Ok(values
+            .into_iter()
+            .map(|value| DataAddress::new(XorName(value)).to_hex())
+            .collect())
+    }
+
+    /// Channel videos with the titles of those that are metadata documents, newest first
+    pub async fn channel_feed(&self, address: &str) -> Result<Vec<FeedVideo>, String> {
+        let mut feed = Vec::new();
+        for video in self.channel_videos(address).await?.into_iter().rev() {
+            // An unreachable video is still listed, just without a title
+            let title = match self.fetch_metadata(&video).await {
+                Ok(metadata) => metadata.map(|metadata| metadata.title),
+                Err(e) => {
+                    println!("Failed to fetch metadata of {video}: {e}");
+                    None
+                }
+            };
+            feed.push(FeedVideo {
+                address: video,
+                title,
+            });
+        }
+        Ok(feed)
+    }
+
+    /// Append a video to the publisher's channel, creating the channel on first use
+    pub async fn publish_to_channel(
+        &self,
+        owner: &SecretKey,
+        video: &DataAddress,
+        wallet: &Wallet,
+    ) -> Result<String, String> {
+        let key = Client::register_key_from_name(owner, CHANNEL_REGISTER_NAME);
+        let register = RegisterAddress::new(key.public_key());
+        let value = video.xorname().0;
+
+        // Only a channel that doesn't exist yet is created, other failures are reported
+        let cost = match self
+            .client
+            .register_update(&key, value, PaymentOption::from(wallet))
+            .await
+        {
+            Err(RegisterError::CannotUpdateNewRegister) => {
+                println!("Creating channel {}", register.to_hex());
+                self.client
+                    .register_create(&key, value, PaymentOption::from(wallet))
+                    .await
+                    .map(|(cost, _)| cost)
+            }
+            result => result,
+        }
+        .map_err(|e| format!("Failed to publish to channel: {e}"))?;
+
+        println!(
+            "Published {} to channel {}, cost: {cost}",
+            video.to_hex(),
+            register.to_hex()
+        );
+        Ok(register.to_hex())
+    }
+
+    /// Load the upload wallet from the `SECRET_KEY` env var or the `secret_key` file in the config dir
+    pub fn load_wallet(&self) -> Result<Wallet, String> {
+        let private_key = load_secret_key()?;
+        Wallet::new_from_private_key(self.client.evm_network().clone(), &private_key)
+            .map_err(|e| format!("Invalid wallet key: {e}"))
+    }
+}
+
+fn load_secret_key() -> Result<String, String> {
+    if let Ok(key) = std::env::var(SECRET_KEY_ENV) {
+        return Ok(key.trim().to_string());
+    }
+
+    let key_path = dirs_next::config_dir()
+        .ok_or_else(|| "Could not find the config directory".to_string())?
+        .join("antube")
+        .join("secret_key");
+
+    std::fs::read_to_string(&key_path)
+        .map(|key| key.trim().to_string())
+        .map_err(|_| {
+            format!(
+                "No wallet key found: set {SECRET_KEY_ENV} or write it to {}",
+                key_path.display()
+            )
+        })
+}
+
+/// Load the key owning the user's channel from `channel_key` in the config dir, creating it on first use
+pub fn load_channel_key() -> Result<SecretKey, String> {
+    let key_path = dirs_next::config_dir()
+        .ok_or_else(|| "Could not find the config directory".to_string())?
+        .join("antube")
+        .join("channel_key");
+
+    match std::fs::read_to_string(&key_path) {
+        Ok(key) => {
+            return SecretKey::from_hex(key.trim())
+                .map_err(|e| format!("Invalid channel key in {}: {e}", key_path.display()));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(format!("Failed to read {}: {e}", key_path.display())),
+    }
+
+    let key = SecretKey::random();
+    if let Some(parent) = key_path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create {}: {e}", parent.display()))?;
+    }
+    // The key controls the channel, so only the user can read it
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(&key_path)
+        .and_then(|mut file| file.write_all(key.to_hex().as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {e}", key_path.display()))?;
+    println!("Created channel key at {}", key_path.display());
+    Ok(key)
+}
+
+impl DataSource for Server {
+    fn open<'a>(&'a self, address: &'a str) -> BoxFuture<'a, Result<ChunkStream, String>> {
+        Box::pin(async move {
+            let stream = self.stream_data(address).await?;
+            let data_size = stream.data_size() as usize;
+            Ok(ChunkStream::new(
+                data_size,
+                stream.map(|chunk_result| chunk_result.map_err(|e| e.to_string())),
+            ))
+        })
+    }
+
+    fn open_ranged<'a>(
+        &'a self,
+        address: &'a str,
+    ) -> BoxFuture<'a, Result<Box<dyn RangeReader>, String>> {
+        Box::pin(async move {
+            let stream = self.stream_data(address).await?;
+            Ok(Box::new(DataRangeReader { stream }) as Box<dyn RangeReader>)
+        })
+    }
+}
+
+/// Random access over public or private data, fetching only the chunks covering each range
+struct DataRangeReader {
+    stream: DataStream,
+}
+
+impl RangeReader for DataRangeReader {
+    fn data_size(&self) -> usize {
+        self.stream.data_size() as usize
+    }
+
+    fn read_range(&mut self, start: usize, len: usize) -> Result<Bytes, String> {
+        let len = len.min(self.data_size().saturating_sub(start));
+        self.stream
+            .range(start as u64, len as u64)
+            .map_err(|e| format!("Failed to fetch range {start}+{len}: {e}"))
+    }
 }
 
-async fn init_client(environment: &str) -> Result<Client, String> {
-    let res = match environment {
-        "local" => Client::init_local().await,
-        "alpha" => Client::init_alpha().await,
-        _ => Client::init().await, // "autonomi"
+async fn init_client(network: &Network) -> Result<Client, String> {
+    let res = match network {
+        Network::Local => Client::init_local().await,
+        Network::Alpha => Client::init_alpha().await,
+        Network::Autonomi => Client::init().await,
+        Network::Custom(definition) => {
+            let config = client_config(definition)?;
+            let timeout = Duration::from_secs(definition.connect_timeout_secs);
+            tokio::time::timeout(timeout, Client::init_with_config(config))
+                .await
+                .map_err(|_| {
+                    format!(
+                        "Timed out connecting to network {:?} after {}s",
+                        definition.name, definition.connect_timeout_secs
+                    )
+                })?
+        }
     };
     res.map_err(|e| {
         println!("Error initializing client: {e}");
         format!("Error initializing client: {e}")
     })
 }
+
+/// Client settings bootstrapping from the peers and paying on the EVM network of a custom network
+fn client_config(definition: &NetworkDefinition) -> Result<ClientConfig, String> {
+    let addrs = definition
+        .peers
+        .iter()
+        .map(|peer| {
+            peer.parse::<Multiaddr>().map_err(|e| {
+                format!(
+                    "Invalid peer {peer:?} of network {:?}: {e}",
+                    definition.name
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let evm_network = match &definition.evm {
+        EvmSettings::ArbitrumOne => autonomi::Network::ArbitrumOne,
+        EvmSettings::ArbitrumSepolia => autonomi::Network::ArbitrumSepoliaTest,
+        EvmSettings::Custom {
+            rpc_url,
+            payment_token_address,
+            data_payments_address,
+        } => autonomi::Network::new_custom(rpc_url, payment_token_address, data_payments_address),
+    };
+
+    Ok(ClientConfig {
+        init_peers_config: InitialPeersConfig {
+            addrs,
+            network_contacts_url: definition.contacts_urls.clone(),
+            local: definition.local,
+            ..Default::default()
+        },
+        evm_network,
+        strategy: Default::default(),
+        network_id: definition.network_id,
+    })
+}
+
+/// Number of encrypted chunks public data of `data_size` bytes is stored as
+pub fn chunk_count(data_size: usize) -> usize {
+    data_size.div_ceil(MAX_CHUNK_SIZE).max(MIN_CHUNK_COUNT)
+}
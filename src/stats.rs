@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Seconds of download history kept for the speed sparkline
+pub const SPEED_HISTORY_SECONDS: usize = 30;
+
+/// Window the rolling throughput is averaged over
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+
+/// Download throughput, first frame and stall statistics of a stream
+#[derive(Debug, Clone)]
+pub struct TransferStats {
+    started_at: Instant,
+    /// Bytes received per second since `started_at`, oldest first
+    buckets: VecDeque<(u64, usize)>,
+    first_frame_after: Option<Duration>,
+    stall_count: usize,
+    stall_time: Duration,
+    /// When the ongoing stall started
+    stalled_since: Option<Instant>,
+}
+
+impl TransferStats {
+    pub fn new(started_at: Instant) -> Self {
+        Self {
+            started_at,
+            buckets: VecDeque::new(),
+            first_frame_after: None,
+            stall_count: 0,
+            stall_time: Duration::ZERO,
+            stalled_since: None,
+        }
+    }
+
+    pub fn record_chunk(&mut self, size: usize, now: Instant) {
+        let second = self.second_of(now);
+        match self.buckets.back_mut() {
+            Some((last, bytes)) if *last == second => *bytes += size,
+            _ => self.buckets.push_back((second, size)),
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(first, _)| first + (SPEED_HISTORY_SECONDS as u64) <= second)
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// Record when the first frame was shown, counted from when the stream was requested
+    pub fn record_first_frame(&mut self, after: Duration) {
+        self.first_frame_after.get_or_insert(after);
+    }
+
+    pub fn time_to_first_frame(&self) -> Option<Duration> {
+        self.first_frame_after
+    }
+
+    /// Average download speed in bytes per second over the last few seconds
+    pub fn throughput(&self, now: Instant) -> f64 {
+        let window_start = now
+            .checked_sub(THROUGHPUT_WINDOW)
+            .unwrap_or(self.started_at)
+            .max(self.started_at);
+        let elapsed = now.saturating_duration_since(window_start).as_secs_f64();
+        if elapsed <= 0.0 {
+            return 0.0;
+        }
+
+        let first_second = self.second_of(window_start);
+        let bytes: usize = self
+            .buckets
+            .iter()
+            .filter(|(second, _)| *second >= first_second)
+            .map(|(_, bytes)| bytes)
+            .sum();
+        bytes as f64 / elapsed.max(1.0)
+    }
+
+    /// Time left to download `remaining` bytes at the current throughput
+    pub fn eta(&self, remaining: usize, now: Instant) -> Option<Duration> {
+        let throughput = self.throughput(now);
+        (throughput > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / throughput))
+    }
+
+    /// Number of stalls so far and their total duration
+    pub fn stalls(&self) -> (usize, Duration) {
+        (self.stall_count, self.stall_time)
+    }
+
+    /// Record the pipeline running out of data, a stall lasts until `end_stall`
+    pub fn start_stall(&mut self, now: Instant) {
+        // Playback can only stall once it has started
+        if self.first_frame_after.is_none() || self.stalled_since.is_some() {
+            return;
+        }
+        self.stall_count += 1;
+        self.stalled_since = Some(now);
+    }
+
+    pub fn end_stall(&mut self, now: Instant) {
+        if let Some(since) = self.stalled_since.take() {
+            self.stall_time += now.saturating_duration_since(since);
+        }
+    }
+
+    /// How long playback has been stalled, if it is stalled right now
+    pub fn current_stall(&self, now: Instant) -> Option<Duration> {
+        self.stalled_since
+            .map(|since| now.saturating_duration_since(since))
+    }
+
+    /// Bytes received in each of the last `SPEED_HISTORY_SECONDS` seconds, oldest first
+    pub fn speed_history(&self, now: Instant) -> Vec<f64> {
+        let current = self.second_of(now);
+        let mut history = vec![0.0; SPEED_HISTORY_SECONDS];
+        for (second, bytes) in &self.buckets {
+            let age = current.saturating_sub(*second) as usize;
+            if age < SPEED_HISTORY_SECONDS {
+                history[SPEED_HISTORY_SECONDS - 1 - age] = *bytes as f64;
+            }
+        }
+        history
+    }
+
+    fn second_of(&self, instant: Instant) -> u64 {
+        instant.saturating_duration_since(self.started_at).as_secs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throughput_and_eta() {
+        let start = Instant::now();
+        let mut stats = TransferStats::new(start);
+        for i in 1..=10 {
+            stats.record_chunk(1000, start + Duration::from_millis(500 * i));
+        }
+
+        let now = start + Duration::from_secs(5);
+        assert_eq!(stats.throughput(now), 2000.0);
+        assert_eq!(stats.eta(4000, now), Some(Duration::from_secs(2)));
+
+        let history = stats.speed_history(now);
+        assert_eq!(history.len(), SPEED_HISTORY_SECONDS);
+        assert_eq!(history[SPEED_HISTORY_SECONDS - 1], 1000.0);
+    }
+
+    #[test]
+    fn test_stalls() {
+        let start = Instant::now();
+        let mut stats = TransferStats::new(start);
+
+        // Slow chunks aren't stalls, running out of data before the first frame isn't either
+        stats.record_chunk(100, start + Duration::from_secs(1));
+        stats.record_chunk(100, start + Duration::from_secs(4));
+        stats.start_stall(start + Duration::from_secs(4));
+        assert_eq!(stats.stalls(), (0, Duration::ZERO));
+
+        stats.record_first_frame(Duration::from_secs(5));
+        stats.start_stall(start + Duration::from_secs(6));
+        stats.start_stall(start + Duration::from_secs(7));
+        assert_eq!(
+            stats.current_stall(start + Duration::from_secs(8)),
+            Some(Duration::from_secs(2))
+        );
+
+        stats.end_stall(start + Duration::from_secs(9));
+        assert_eq!(stats.stalls(), (1, Duration::from_secs(3)));
+        assert_eq!(stats.current_stall(start + Duration::from_secs(10)), None);
+    }
+}
@@ -0,0 +1,106 @@
+/// Prefix of links naming the network and start position along with the address
+pub const SCHEME: &str = "antube://";
+
+/// What to play, parsed from an `antube://<network>/<address>?t=<seconds>` URI or a bare address
+#[derive(Debug, Clone, PartialEq)]
+pub struct AntubeUri {
+    /// Network of the address, the selected one when `None`
+    pub network: Option<String>,
+    pub address: String,
+    /// Playback position to start from, in seconds
+    pub start: Option<f64>,
+}
+
+impl AntubeUri {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let Some(rest) = input.strip_prefix(SCHEME) else {
+            return Ok(Self {
+                network: None,
+                address: input.to_string(),
+                start: None,
+            });
+        };
+
+        let (path, query) = match rest.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (rest, None),
+        };
+        let (network, address) = path
+            .trim_end_matches('/')
+            .split_once('/')
+            .filter(|(network, address)| {
+                !network.is_empty() && !address.is_empty() && !address.contains('/')
+            })
+            .ok_or_else(|| {
+                format!("Invalid URI {input:?}, expected {SCHEME}<network>/<address>")
+            })?;
+
+        let mut start = None;
+        // Other parameters are ignored, for links made by newer versions
+        for (key, value) in query
+            .into_iter()
+            .flat_map(|query| query.split('&'))
+            .filter_map(|param| param.split_once('='))
+        {
+            if key == "t" {
+                start = Some(parse_seconds(value)?);
+            }
+        }
+
+        Ok(Self {
+            network: Some(network.to_string()),
+            address: address.to_string(),
+            start,
+        })
+    }
+}
+
+impl std::fmt::Display for AntubeUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Some(network) = &self.network else {
+            return write!(f, "{}", self.address);
+        };
+        write!(f, "{SCHEME}{network}/{}", self.address)?;
+        match self.start {
+            Some(start) => write!(f, "?t={start}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Start time in seconds, with or without an `s` suffix
+fn parse_seconds(value: &str) -> Result<f64, String> {
+    value
+        .strip_suffix('s')
+        .unwrap_or(value)
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .ok_or_else(|| format!("Invalid start time {value:?}, expected seconds"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uri() {
+        let uri = AntubeUri::parse("antube://local/abc123?t=90").unwrap();
+        assert_eq!(uri.network.as_deref(), Some("local"));
+        assert_eq!(uri.address, "abc123");
+        assert_eq!(uri.start, Some(90.0));
+        assert_eq!(AntubeUri::parse(&uri.to_string()), Ok(uri));
+
+        let uri = AntubeUri::parse(" antube://testnet/abc123/?t=1.5s&utm=x ").unwrap();
+        assert_eq!(uri.network.as_deref(), Some("testnet"));
+        assert_eq!(uri.start, Some(1.5));
+
+        // Bare addresses use the selected network
+        let uri = AntubeUri::parse("abc123").unwrap();
+        assert_eq!((uri.network, uri.address.as_str()), (None, "abc123"));
+
+        assert!(AntubeUri::parse("antube://abc123").is_err());
+        assert!(AntubeUri::parse("antube://local/abc123?t=soon").is_err());
+    }
+}
@@ -1,7 +1,12 @@
+use crate::config::PipelineSettings;
+
 use gst::prelude::*;
 use gstreamer as gst;
 use gstreamer_app as gst_app;
+use gstreamer_video as gst_video;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
 
 #[derive(Debug)]
 pub struct StreamError(String);
@@ -26,33 +31,127 @@ struct PipelineElements {
     audiosink: gst::Element,
 }
 
+/// Where decoded video frames are displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoOutput {
+    /// Frames are kept for the UI to draw inline
+    Embedded,
+    /// A separate native window per stream (glimagesink)
+    Native,
+    /// Decode without displaying anything (fakesink), for headless playback
+    Fake,
+}
+
+/// A decoded RGBA video frame, rows tightly packed
+pub struct VideoFrame {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Pipeline state reported on the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineState {
+    Stopped,
+    Ready,
+    Paused,
+    Playing,
+}
+
+impl From<gst::State> for PipelineState {
+    fn from(state: gst::State) -> Self {
+        match state {
+            gst::State::Ready => PipelineState::Ready,
+            gst::State::Paused => PipelineState::Paused,
+            gst::State::Playing => PipelineState::Playing,
+            _ => PipelineState::Stopped,
+        }
+    }
+}
+
+/// Bus messages and appsrc underruns forwarded to the owner of the pipeline
+#[derive(Debug, Clone)]
+pub enum PipelineEvent {
+    Error {
+        message: String,
+    },
+    PlaybackFinished,
+    Buffering {
+        percent: i32,
+    },
+    StateChanged {
+        state: PipelineState,
+    },
+    /// The appsrc queue ran dry before the end of the data, until `Refilled`
+    Underrun,
+    Refilled,
+}
+
+type PipelineEventHandler = Arc<dyn Fn(PipelineEvent) + Send + Sync>;
+
+/// Byte offset requested by the appsrc `seek-data` callback, waiting to be served
+#[derive(Default)]
+struct SeekRequest {
+    offset: Mutex<Option<u64>>,
+    notify: Notify,
+}
+
 pub struct VideoStreamer {
     appsrc: gst_app::AppSrc,
-    #[allow(dead_code)]
-    pipeline: gst::Pipeline, // Kept for potential future use
-    is_eos: AtomicBool,
+    pipeline: gst::Pipeline,
+    is_eos: Arc<AtomicBool>,
+    seek_request: Arc<SeekRequest>,
+    /// The appsrc queue is empty and waiting for data
+    starved: Arc<AtomicBool>,
+    on_event: PipelineEventHandler,
+    video_output: VideoOutput,
+    latest_frame: Arc<Mutex<Option<VideoFrame>>>,
 }
 
 impl VideoStreamer {
-    pub fn new() -> Result<Self, StreamError> {
+    pub fn new(
+        total_size: usize,
+        video_output: VideoOutput,
+        settings: &PipelineSettings,
+        on_event: impl Fn(PipelineEvent) + Send + Sync + 'static,
+    ) -> Result<Self, StreamError> {
         Self::init_gstreamer()?;
 
         let pipeline = gst::Pipeline::new();
-        let elements = Self::create_pipeline_elements()?;
+        let latest_frame = Arc::new(Mutex::new(None));
+        let elements =
+            Self::create_pipeline_elements(video_output, settings, latest_frame.clone())?;
 
         Self::add_elements_to_pipeline(&pipeline, &elements)?;
         Self::link_static_elements(&elements)?;
 
         Self::setup_dynamic_linking(&elements);
-        let appsrc = Self::configure_appsrc(elements.appsrc)?;
-        Self::setup_bus_monitoring(&pipeline);
+        let is_eos = Arc::new(AtomicBool::new(false));
+        let seek_request = Arc::new(SeekRequest::default());
+        let starved = Arc::new(AtomicBool::new(false));
+        let on_event: PipelineEventHandler = Arc::new(on_event);
+        let appsrc = Self::configure_appsrc(
+            elements.appsrc,
+            total_size,
+            settings.appsrc_max_bytes,
+            is_eos.clone(),
+            seek_request.clone(),
+            starved.clone(),
+            on_event.clone(),
+        )?;
+        Self::setup_bus_monitoring(&pipeline, on_event.clone());
 
         Self::start_pipeline(&pipeline)?;
 
         Ok(Self {
             appsrc,
             pipeline,
-            is_eos: AtomicBool::new(false),
+            is_eos,
+            seek_request,
+            starved,
+            on_event,
+            video_output,
+            latest_frame,
         })
     }
 
@@ -60,22 +159,41 @@ impl VideoStreamer {
         gst::init().map_err(|e| StreamError(format!("Failed to initialize GStreamer: {}", e)))
     }
 
-    fn create_pipeline_elements() -> Result<PipelineElements, StreamError> {
+    fn create_pipeline_elements(
+        video_output: VideoOutput,
+        settings: &PipelineSettings,
+        latest_frame: Arc<Mutex<Option<VideoFrame>>>,
+    ) -> Result<PipelineElements, StreamError> {
+        let videosink = match video_output {
+            VideoOutput::Native => Self::create_element(&settings.video_sink, None)?,
+            VideoOutput::Embedded => Self::create_frame_sink(latest_frame)?,
+            VideoOutput::Fake => Self::create_fake_sink("fakevideosink")?,
+        };
+        let audiosink = match video_output {
+            VideoOutput::Fake => Self::create_fake_sink("fakeaudiosink")?,
+            _ => Self::create_element(&settings.audio_sink, None)?,
+        };
+
         let elements = PipelineElements {
             appsrc: Self::create_element("appsrc", Some("src"))?,
             decodebin: Self::create_element("decodebin", None)?,
             videoconvert: Self::create_element("videoconvert", None)?,
-            videosink: Self::create_element("glimagesink", None)?,
+            videosink,
             audioconvert: Self::create_element("audioconvert", None)?,
-            audiosink: Self::create_element("autoaudiosink", None)?,
+            audiosink,
         };
 
         // Set buffer limits on decodebin to prevent memory growth
         if let Some(decodebin) = elements.decodebin.dynamic_cast_ref::<gst::Element>() {
-            // Set maximum internal queue size to 2MB per stream
-            decodebin.set_property("max-size-bytes", 2 * 1024 * 1024u32);
-            decodebin.set_property("max-size-time", 2_000_000_000u64); // 2 seconds
-            println!("Configured decodebin with memory limits");
+            decodebin.set_property("max-size-bytes", settings.decodebin_max_size_bytes);
+            decodebin.set_property(
+                "max-size-time",
+                settings.decodebin_max_size_ms.saturating_mul(1_000_000), // nanoseconds
+            );
+            println!(
+                "Configured decodebin with {} byte and {}ms queue limits",
+                settings.decodebin_max_size_bytes, settings.decodebin_max_size_ms
+            );
         }
 
         Ok(elements)
@@ -91,6 +209,72 @@ impl VideoStreamer {
             .map_err(|e| StreamError(format!("Failed to create {}: {}", factory_name, e)))
     }
 
+    /// A fakesink consuming data at playback speed
+    fn create_fake_sink(name: &str) -> Result<gst::Element, StreamError> {
+        let sink = Self::create_element("fakesink", Some(name))?;
+        sink.set_property("sync", true);
+        Ok(sink)
+    }
+
+    /// An appsink converting frames to RGBA and keeping the latest one for the UI
+    fn create_frame_sink(
+        latest_frame: Arc<Mutex<Option<VideoFrame>>>,
+    ) -> Result<gst::Element, StreamError> {
+        let caps = gst_video::VideoCapsBuilder::new()
+            .format(gst_video::VideoFormat::Rgba)
+            .build();
+
+        let appsink = gst_app::AppSink::builder()
+            .name("framesink")
+            .caps(&caps)
+            .max_buffers(1)
+            .drop(true)
+            .sync(true)
+            .build();
+
+        appsink.set_callbacks(
+            gst_app::AppSinkCallbacks::builder()
+                .new_sample(move |sink| Self::handle_new_sample(sink, &latest_frame))
+                .build(),
+        );
+
+        println!("Configured AppSink for embedded video output");
+        Ok(appsink.upcast())
+    }
+
+    fn handle_new_sample(
+        sink: &gst_app::AppSink,
+        latest_frame: &Mutex<Option<VideoFrame>>,
+    ) -> Result<gst::FlowSuccess, gst::FlowError> {
+        let sample = sink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+        let (Some(buffer), Some(caps)) = (sample.buffer(), sample.caps()) else {
+            return Err(gst::FlowError::Error);
+        };
+
+        let info =
+            gst_video::VideoInfo::from_caps(caps).map_err(|_| gst::FlowError::NotNegotiated)?;
+        let frame = gst_video::VideoFrameRef::from_buffer_ref_readable(buffer, &info)
+            .map_err(|_| gst::FlowError::Error)?;
+
+        let width = info.width() as usize;
+        let height = info.height() as usize;
+        let stride = frame.plane_stride()[0] as usize;
+        let data = frame.plane_data(0).map_err(|_| gst::FlowError::Error)?;
+
+        // Drop the row padding so the UI gets tightly packed pixels
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for row in data.chunks(stride).take(height) {
+            pixels.extend_from_slice(&row[..width * 4]);
+        }
+
+        *latest_frame.lock().unwrap() = Some(VideoFrame {
+            width,
+            height,
+            pixels,
+        });
+        Ok(gst::FlowSuccess::Ok)
+    }
+
     fn add_elements_to_pipeline(
         pipeline: &gst::Pipeline,
         elements: &PipelineElements,
@@ -191,39 +375,83 @@ impl VideoStreamer {
         }
     }
 
-    fn configure_appsrc(appsrc: gst::Element) -> Result<gst_app::AppSrc, StreamError> {
+    fn configure_appsrc(
+        appsrc: gst::Element,
+        total_size: usize,
+        max_bytes: u64,
+        is_eos: Arc<AtomicBool>,
+        seek_request: Arc<SeekRequest>,
+        starved: Arc<AtomicBool>,
+        on_event: PipelineEventHandler,
+    ) -> Result<gst_app::AppSrc, StreamError> {
         let appsrc = appsrc
             .dynamic_cast::<gst_app::AppSrc>()
             .map_err(|_| StreamError("Element is not AppSrc".to_string()))?;
 
         appsrc.set_format(gst::Format::Bytes);
-        appsrc.set_stream_type(gst_app::AppStreamType::Stream);
+        appsrc.set_stream_type(gst_app::AppStreamType::Seekable);
+        appsrc.set_size(total_size as i64);
+
+        // On seek, appsrc flushes its queue and asks for data from the new offset,
+        // the streaming task picks the request up and fetches that range instead
+        let seek_starved = starved.clone();
+        let need_is_eos = is_eos.clone();
+        appsrc.set_callbacks(
+            gst_app::AppSrcCallbacks::builder()
+                .seek_data(move |_appsrc, offset| {
+                    println!("AppSrc requested data from byte offset {}", offset);
+                    is_eos.store(false, Ordering::Relaxed);
+                    // Waiting for the data of a seek isn't an underrun
+                    seek_starved.store(true, Ordering::Relaxed);
+                    *seek_request.offset.lock().unwrap() = Some(offset);
+                    seek_request.notify.notify_one();
+                    true
+                })
+                .need_data(move |appsrc, _length| {
+                    // An empty queue before the end means the download fell behind playback
+                    if appsrc.current_level_bytes() == 0
+                        && !need_is_eos.load(Ordering::Relaxed)
+                        && !starved.swap(true, Ordering::Relaxed)
+                    {
+                        println!("AppSrc queue ran dry");
+                        on_event(PipelineEvent::Underrun);
+                    }
+                })
+                .build(),
+        );
 
         // Set a much smaller buffer limit to prevent memory growth
-        // Only buffer 5MB internally in GStreamer (we already have 40MB prebuffered)
-        appsrc.set_max_bytes(5 * 1024 * 1024u64);
+        // Only buffer a few MB internally in GStreamer (we already have a prebuffer of several seconds)
+        appsrc.set_max_bytes(max_bytes);
 
         // Block when the internal queue is full instead of dropping data
         appsrc.set_block(true);
 
         // Let decodebin auto-detect the format instead of setting caps
-        println!("Configured AppSrc with 5MB buffer limit for memory control");
+        println!("Configured AppSrc with {max_bytes} byte buffer limit for memory control");
         Ok(appsrc)
     }
 
-    fn setup_bus_monitoring(pipeline: &gst::Pipeline) {
+    fn setup_bus_monitoring(pipeline: &gst::Pipeline, on_event: PipelineEventHandler) {
         let bus = match pipeline.bus() {
             Some(bus) => bus,
             None => return,
         };
 
+        // Handle messages synchronously on the posting thread, there is no GLib main loop
+        // to dispatch bus watches, and drop them so they don't pile up in the bus queue
         let pipeline_weak = pipeline.downgrade();
-        bus.connect_message(None, move |_bus, msg| {
-            Self::handle_bus_message(msg, &pipeline_weak);
+        bus.set_sync_handler(move |_bus, msg| {
+            Self::handle_bus_message(msg, &pipeline_weak, on_event.as_ref());
+            gst::BusSyncReply::Drop
         });
     }
 
-    fn handle_bus_message(msg: &gst::Message, pipeline_weak: &gst::glib::WeakRef<gst::Pipeline>) {
+    fn handle_bus_message(
+        msg: &gst::Message,
+        pipeline_weak: &gst::glib::WeakRef<gst::Pipeline>,
+        on_event: &(dyn Fn(PipelineEvent) + Send + Sync),
+    ) {
         use gst::MessageView;
         match msg.view() {
             MessageView::StateChanged(state_changed) => {
@@ -234,6 +462,9 @@ impl VideoStreamer {
                             state_changed.old(),
                             state_changed.current()
                         );
+                        on_event(PipelineEvent::StateChanged {
+                            state: state_changed.current().into(),
+                        });
 
                         if state_changed.current() == gst::State::Null {
                             println!("Pipeline went to NULL state");
@@ -255,7 +486,7 @@ impl VideoStreamer {
                     }
 
                     // Monitor video and audio sink state changes for debugging
-                    if element_name.contains("glimagesink") {
+                    if element_name.contains("glimagesink") || element_name.contains("framesink") {
                         println!(
                             "Video sink {} state: {:?} -> {:?}",
                             element_name,
@@ -285,12 +516,17 @@ impl VideoStreamer {
                 {
                     let element_name = element.name();
                     if element_name.contains("glimagesink")
+                        || element_name.contains("framesink")
                         || element_name.contains("audiosink")
                         || element_name.contains("decodebin")
                     {
                         println!("Critical pipeline element error: {}", element_name);
                     }
                 }
+
+                on_event(PipelineEvent::Error {
+                    message: error.error().to_string(),
+                });
             }
             MessageView::Warning(warning) => {
                 println!("Pipeline warning: {}", warning.error());
@@ -300,6 +536,13 @@ impl VideoStreamer {
             }
             MessageView::Eos(_) => {
                 println!("Pipeline received End-of-Stream");
+                on_event(PipelineEvent::PlaybackFinished);
+            }
+            MessageView::Buffering(buffering) => {
+                println!("Buffering: {}%", buffering.percent());
+                on_event(PipelineEvent::Buffering {
+                    percent: buffering.percent(),
+                });
             }
             MessageView::StreamStart(_) => {
                 println!("Stream started");
@@ -314,7 +557,6 @@ impl VideoStreamer {
                     MessageView::Element(element_msg) => {
                         println!("Element message: {:?}", element_msg);
                     }
-                    MessageView::Buffering(_) => println!("Buffering message: {:?}", msg.view()),
                     MessageView::AsyncDone(_) => println!("Async done: {:?}", msg.view()),
                     MessageView::Latency(_) => println!("Latency message: {:?}", msg.view()),
                     MessageView::Qos(_) => println!("QoS message: {:?}", msg.view()),
@@ -351,12 +593,20 @@ impl VideoStreamer {
         match self.appsrc.push_buffer(buffer) {
             Ok(_) => {
                 println!("Successfully pushed buffer to AppSrc");
+                if self.starved.swap(false, Ordering::Relaxed) {
+                    (self.on_event)(PipelineEvent::Refilled);
+                }
                 Ok(())
             }
             Err(gst::FlowError::Eos) => {
                 println!("AppSrc returned EOS");
                 self.signal_end_of_stream()
             }
+            Err(gst::FlowError::Flushing) => {
+                // A seek is in progress, the data will be requested again from the new offset
+                println!("AppSrc is flushing, dropping buffer");
+                Ok(())
+            }
             Err(e) => Err(format!("Failed to push buffer to AppSrc: {:?}", e)),
         }
     }
@@ -373,6 +623,82 @@ impl VideoStreamer {
             .map_err(|e| format!("Failed to signal end of stream: {:?}", e))
             .map(|_| ())
     }
+
+    /// Update the size of the data, for streams whose size changes while playing
+    pub fn set_size(&self, size: usize) {
+        self.appsrc.set_size(size as i64);
+    }
+
+    pub fn pause(&self) -> Result<(), String> {
+        println!("Pausing pipeline");
+        self.set_pipeline_state(gst::State::Paused)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        println!("Resuming pipeline");
+        self.set_pipeline_state(gst::State::Playing)
+    }
+
+    /// Tear down the pipeline, no more data is accepted afterwards
+    pub fn stop(&self) -> Result<(), String> {
+        println!("Stopping pipeline");
+        self.is_eos.store(true, Ordering::Relaxed);
+        self.set_pipeline_state(gst::State::Null)
+    }
+
+    fn set_pipeline_state(&self, state: gst::State) -> Result<(), String> {
+        self.pipeline
+            .set_state(state)
+            .map(|_| ())
+            .map_err(|e| format!("Failed to set pipeline state to {:?}: {}", state, e))
+    }
+
+    pub fn video_output(&self) -> VideoOutput {
+        self.video_output
+    }
+
+    /// Take the most recent decoded frame, if a new one arrived since the last call
+    pub fn take_frame(&self) -> Option<VideoFrame> {
+        self.latest_frame.lock().unwrap().take()
+    }
+
+    /// Take the byte offset of the last seek requested by the pipeline, if any
+    pub fn take_seek_request(&self) -> Option<u64> {
+        self.seek_request.offset.lock().unwrap().take()
+    }
+
+    /// Wait until the pipeline requests data from a new byte offset
+    pub async fn wait_for_seek_request(&self) -> u64 {
+        loop {
+            if let Some(offset) = self.take_seek_request() {
+                return offset;
+            }
+            self.seek_request.notify.notified().await;
+        }
+    }
+
+    pub fn position_seconds(&self) -> Option<f64> {
+        self.pipeline
+            .query_position::<gst::ClockTime>()
+            .map(|position| position.mseconds() as f64 / 1000.0)
+    }
+
+    pub fn duration_seconds(&self) -> Option<f64> {
+        self.pipeline
+            .query_duration::<gst::ClockTime>()
+            .map(|duration| duration.mseconds() as f64 / 1000.0)
+    }
+
+    /// Seek playback to `seconds`, the pipeline then requests the matching byte range
+    pub fn seek_to(&self, seconds: f64) -> Result<(), String> {
+        println!("Seeking to {:.1}s", seconds);
+        self.pipeline
+            .seek_simple(
+                gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+                gst::ClockTime::from_mseconds((seconds.max(0.0) * 1000.0) as u64),
+            )
+            .map_err(|e| format!("Failed to seek: {e}"))
+    }
 }
 
 // Drop implementation removed to prevent blocking during application shutdown
@@ -399,7 +725,13 @@ mod tests {
         println!("Read {} bytes from MP4 file", buffer.len());
 
         // Create video streamer
-        let streamer = VideoStreamer::new().expect("Failed to create VideoStreamer");
+        let streamer = VideoStreamer::new(
+            buffer.len(),
+            VideoOutput::Native,
+            &PipelineSettings::default(),
+            |_| {},
+        )
+        .expect("Failed to create VideoStreamer");
 
         // Stream the file in 1MB chunks (similar to our main app)
         let chunk_size = 1024 * 1024; // 1MB
@@ -464,7 +796,13 @@ mod tests {
         println!("Read {} bytes from network MP4 file", buffer.len());
 
         // Create video streamer
-        let streamer = VideoStreamer::new().expect("Failed to create VideoStreamer");
+        let streamer = VideoStreamer::new(
+            buffer.len(),
+            VideoOutput::Native,
+            &PipelineSettings::default(),
+            |_| {},
+        )
+        .expect("Failed to create VideoStreamer");
 
         // Check the first few bytes to see the MP4 headers
         println!("First 32 bytes: {:?}", &buffer[..32.min(buffer.len())]);